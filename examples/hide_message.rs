@@ -0,0 +1,24 @@
+// hides a message in a chunk of a freshly generated image and reads it back, in memory
+#![allow(clippy::needless_return)]
+
+use std::str::FromStr;
+
+use pngme::deflate::CompressionLevel;
+use pngme::image::Image;
+use pngme::image_header::ColorType;
+use pngme::{Chunk, ChunkType, Png, Result};
+
+fn main() -> Result<()> {
+    let pixels: Vec<u8> = (0..(16 * 16 * 3)).map(|i| (i % 256) as u8).collect();
+    let image = Image::new(16, 16, ColorType::Rgb, 8, pixels)?;
+    let mut png = image.to_png(CompressionLevel::Default)?;
+
+    let chunk_type = ChunkType::from_str("ruSt")?;
+    png.insert_before_iend(Chunk::new(chunk_type, b"hello from pngme".to_vec())?);
+
+    let png = Png::from_bytes(&png.bytes())?;
+    let chunk = png.search_chunk("ruSt").expect("the message chunk is kept");
+    println!("Decoded Message: {}", chunk.data_str()?);
+
+    return Ok(());
+}
@@ -0,0 +1,22 @@
+// prints the chunk layout of a PNG file, e.g. `cargo run --example list_chunks -- dice.png`
+#![allow(clippy::needless_return)]
+
+use std::env;
+
+use pngme::ops;
+use pngme::{Error, Result};
+
+fn main() -> Result<()> {
+    let fname = env::args().nth(1).ok_or(Error::InvalidArguments)?;
+    let png = ops::read_png(&fname)?;
+
+    println!("{}", png.header_info()?);
+    for chunk in png.chunks() {
+        println!("{} ({} bytes)", chunk.chunk_type().str(), chunk.length());
+    }
+    for violation in png.validate() {
+        println!("{}", violation);
+    }
+
+    return Ok(());
+}
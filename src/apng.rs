@@ -0,0 +1,861 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::deflate::CompressionLevel;
+use crate::error::{Error, Result};
+use crate::image::Image;
+use crate::image_header::ImageHeader;
+use crate::png::Png;
+
+// chunks of the pixel data itself, which a frame has its own version of
+const IMAGE_DATA_CHUNKS: [&str; 5] = ["IHDR", "acTL", "fcTL", "IDAT", "fdAT"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+impl DisposeOp {
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => return Ok(Self::None),
+            1 => return Ok(Self::Background),
+            2 => return Ok(Self::Previous),
+            _ => {
+                return Err(malformed(
+                    FrameControl::CHUNK_TYPE,
+                    format!("invalid dispose op {}", value),
+                ))
+            }
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::None => return 0,
+            Self::Background => return 1,
+            Self::Previous => return 2,
+        }
+    }
+}
+
+impl fmt::Display for DisposeOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::None => "None",
+            Self::Background => "Background",
+            Self::Previous => "Previous",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+impl BlendOp {
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => return Ok(Self::Source),
+            1 => return Ok(Self::Over),
+            _ => {
+                return Err(malformed(
+                    FrameControl::CHUNK_TYPE,
+                    format!("invalid blend op {}", value),
+                ))
+            }
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::Source => return 0,
+            Self::Over => return 1,
+        }
+    }
+}
+
+impl fmt::Display for BlendOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Source => "Source",
+            Self::Over => "Over",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationControl {
+    m_num_frames: u32,
+    m_num_plays: u32,
+}
+
+impl AnimationControl {
+    pub const CHUNK_TYPE: &'static str = "acTL";
+
+    // `num_plays` of 0 loops forever
+    pub fn new(num_frames: u32, num_plays: u32) -> Result<Self> {
+        if num_frames == 0 {
+            return Err(malformed(Self::CHUNK_TYPE, String::from("zero frames")));
+        }
+
+        return Ok(Self {
+            m_num_frames: num_frames,
+            m_num_plays: num_plays,
+        });
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        let data = expect_chunk(chunk, Self::CHUNK_TYPE, 8)?;
+        return Self::new(read_u32(data, 0), read_u32(data, 4));
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data: Vec<u8> = Vec::with_capacity(8);
+        data.extend_from_slice(&self.m_num_frames.to_be_bytes());
+        data.extend_from_slice(&self.m_num_plays.to_be_bytes());
+
+        return Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE)?, data);
+    }
+
+    pub fn num_frames(&self) -> u32 {
+        return self.m_num_frames;
+    }
+
+    pub fn num_plays(&self) -> u32 {
+        return self.m_num_plays;
+    }
+}
+
+impl fmt::Display for AnimationControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AnimationControl: {{ Frames: {}, Plays: {}}}",
+            self.num_frames(),
+            self.num_plays()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameControl {
+    m_sequence_number: u32,
+    m_width: u32,
+    m_height: u32,
+    m_x_offset: u32,
+    m_y_offset: u32,
+    m_delay_num: u16,
+    m_delay_den: u16,
+    m_dispose_op: DisposeOp,
+    m_blend_op: BlendOp,
+}
+
+impl FrameControl {
+    pub const CHUNK_TYPE: &'static str = "fcTL";
+
+    // a frame shown for 0 seconds, with no disposal and no blending; the sequence number is
+    // assigned when the animation is built
+    pub fn new(width: u32, height: u32, x_offset: u32, y_offset: u32) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+
+        return Ok(Self {
+            m_sequence_number: 0,
+            m_width: width,
+            m_height: height,
+            m_x_offset: x_offset,
+            m_y_offset: y_offset,
+            m_delay_num: 0,
+            m_delay_den: 0,
+            m_dispose_op: DisposeOp::None,
+            m_blend_op: BlendOp::Source,
+        });
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        let data = expect_chunk(chunk, Self::CHUNK_TYPE, 26)?;
+        let mut frame_control = Self::new(
+            read_u32(data, 4),
+            read_u32(data, 8),
+            read_u32(data, 12),
+            read_u32(data, 16),
+        )?;
+        frame_control.m_sequence_number = read_u32(data, 0);
+        frame_control.set_delay(
+            u16::from_be_bytes([data[20], data[21]]),
+            u16::from_be_bytes([data[22], data[23]]),
+        );
+        frame_control.set_dispose_op(DisposeOp::from_u8(data[24])?);
+        frame_control.set_blend_op(BlendOp::from_u8(data[25])?);
+
+        return Ok(frame_control);
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data: Vec<u8> = Vec::with_capacity(26);
+        data.extend_from_slice(&self.m_sequence_number.to_be_bytes());
+        data.extend_from_slice(&self.m_width.to_be_bytes());
+        data.extend_from_slice(&self.m_height.to_be_bytes());
+        data.extend_from_slice(&self.m_x_offset.to_be_bytes());
+        data.extend_from_slice(&self.m_y_offset.to_be_bytes());
+        data.extend_from_slice(&self.m_delay_num.to_be_bytes());
+        data.extend_from_slice(&self.m_delay_den.to_be_bytes());
+        data.push(self.m_dispose_op.value());
+        data.push(self.m_blend_op.value());
+
+        return Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE)?, data);
+    }
+
+    // a denominator of 0 stands for 100, i.e. the delay is in hundredths of a second
+    pub fn set_delay(&mut self, num: u16, den: u16) {
+        self.m_delay_num = num;
+        self.m_delay_den = den;
+    }
+
+    pub fn set_dispose_op(&mut self, dispose_op: DisposeOp) {
+        self.m_dispose_op = dispose_op;
+    }
+
+    pub fn set_blend_op(&mut self, blend_op: BlendOp) {
+        self.m_blend_op = blend_op;
+    }
+
+    pub(crate) fn set_sequence_number(&mut self, sequence_number: u32) {
+        self.m_sequence_number = sequence_number;
+    }
+
+    pub fn sequence_number(&self) -> u32 {
+        return self.m_sequence_number;
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.m_width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.m_height;
+    }
+
+    pub fn x_offset(&self) -> u32 {
+        return self.m_x_offset;
+    }
+
+    pub fn y_offset(&self) -> u32 {
+        return self.m_y_offset;
+    }
+
+    pub fn delay_num(&self) -> u16 {
+        return self.m_delay_num;
+    }
+
+    pub fn delay_den(&self) -> u16 {
+        return self.m_delay_den;
+    }
+
+    pub fn delay_secs(&self) -> f64 {
+        let den = if self.m_delay_den == 0 {
+            100
+        } else {
+            self.m_delay_den
+        };
+        return self.m_delay_num as f64 / den as f64;
+    }
+
+    pub fn dispose_op(&self) -> DisposeOp {
+        return self.m_dispose_op;
+    }
+
+    pub fn blend_op(&self) -> BlendOp {
+        return self.m_blend_op;
+    }
+}
+
+impl fmt::Display for FrameControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FrameControl: {{ Sequence: {}, Width: {}, Height: {}, XOffset: {}, YOffset: {}, Delay: {}/{}, DisposeOp: {}, BlendOp: {}}}",
+            self.sequence_number(),
+            self.width(),
+            self.height(),
+            self.x_offset(),
+            self.y_offset(),
+            self.delay_num(),
+            self.delay_den(),
+            self.dispose_op(),
+            self.blend_op()
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    m_control: FrameControl,
+    // zlib stream of the frame, from its IDAT or fdAT chunks
+    m_data: Vec<u8>,
+    m_is_default_image: bool,
+}
+
+impl Frame {
+    pub fn control(&self) -> &FrameControl {
+        return &self.m_control;
+    }
+
+    pub fn data(&self) -> &[u8] {
+        return &self.m_data;
+    }
+
+    // whether the frame is the IDAT image shown by decoders without APNG support
+    pub fn is_default_image(&self) -> bool {
+        return self.m_is_default_image;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    m_control: AnimationControl,
+    m_frames: Vec<Frame>,
+}
+
+impl Animation {
+    // parses the APNG chunks of `png`, checking sequence numbers and frame regions
+    pub fn from_png(png: &Png) -> Result<Self> {
+        let header = png.header_info()?;
+        let mut control: Option<AnimationControl> = None;
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut next_sequence_number: u32 = 0;
+        let mut seen_idat = false;
+
+        let mut check_sequence_number = |found: u32| {
+            if found != next_sequence_number {
+                return Err(Error::InvalidSequenceNumber {
+                    expected: next_sequence_number,
+                    found,
+                });
+            }
+            next_sequence_number += 1;
+            return Ok(());
+        };
+
+        for chunk in png.chunks() {
+            match chunk.chunk_type().str() {
+                AnimationControl::CHUNK_TYPE => {
+                    if control.is_some() {
+                        return Err(invalid("more than one acTL chunk"));
+                    }
+                    if seen_idat {
+                        return Err(invalid("acTL chunk after the image data"));
+                    }
+                    control = Some(AnimationControl::from_chunk(chunk)?);
+                }
+                FrameControl::CHUNK_TYPE => {
+                    let frame_control = FrameControl::from_chunk(chunk)?;
+                    check_sequence_number(frame_control.sequence_number())?;
+                    let right = frame_control.x_offset() as u64 + frame_control.width() as u64;
+                    let bottom = frame_control.y_offset() as u64 + frame_control.height() as u64;
+                    if right > header.width() as u64 || bottom > header.height() as u64 {
+                        return Err(Error::InvalidAnimation(format!(
+                            "frame {} lies outside the {}x{} canvas",
+                            frames.len(),
+                            header.width(),
+                            header.height()
+                        )));
+                    }
+                    frames.push(Frame {
+                        m_control: frame_control,
+                        m_data: Vec::new(),
+                        m_is_default_image: false,
+                    });
+                }
+                "IDAT" if !seen_idat => {
+                    seen_idat = true;
+                    // an fcTL before the first IDAT makes the default image the first frame
+                    if let Some(frame) = frames.last_mut() {
+                        frame.m_is_default_image = true;
+                        frame.m_data = png.compressed_image_data();
+                    }
+                }
+                "fdAT" => {
+                    let data = chunk.data();
+                    if data.len() < 4 {
+                        return Err(malformed("fdAT", String::from("missing sequence number")));
+                    }
+                    check_sequence_number(read_u32(data, 0))?;
+                    match frames.last_mut() {
+                        Some(frame) if seen_idat && !frame.m_is_default_image => {
+                            frame.m_data.extend_from_slice(&data[4..])
+                        }
+                        _ => return Err(invalid("fdAT chunk without a frame control chunk")),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let control = control.ok_or(Error::ChunkNotFound(String::from(
+            AnimationControl::CHUNK_TYPE,
+        )))?;
+        if control.num_frames() as usize != frames.len() {
+            return Err(Error::InvalidAnimation(format!(
+                "acTL declares {} frames, found {}",
+                control.num_frames(),
+                frames.len()
+            )));
+        }
+        if let Some(index) = frames.iter().position(|frame| frame.m_data.is_empty()) {
+            return Err(Error::InvalidAnimation(format!(
+                "frame {} has no image data",
+                index
+            )));
+        }
+        if frames[0].is_default_image() {
+            let first = frames[0].control();
+            if (first.x_offset(), first.y_offset()) != (0, 0)
+                || (first.width(), first.height()) != (header.width(), header.height())
+            {
+                return Err(invalid(
+                    "the default image frame must cover the whole canvas",
+                ));
+            }
+        }
+
+        return Ok(Self {
+            m_control: control,
+            m_frames: frames,
+        });
+    }
+
+    // APNG whose canvas is the first frame; every frame needs the first one's pixel format and
+    // the size of its `FrameControl`
+    pub fn build(
+        frames: &[(Image, FrameControl)],
+        num_plays: u32,
+        level: CompressionLevel,
+    ) -> Result<Png> {
+        let (first, _) = frames.first().ok_or(invalid("no frames"))?;
+        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut sequence_number: u32 = 0;
+
+        for (index, (image, frame_control)) in frames.iter().enumerate() {
+            if image.color_type() != first.color_type()
+                || image.bit_depth() != first.bit_depth()
+                || image.palette() != first.palette()
+                || image.transparency() != first.transparency()
+            {
+                return Err(Error::InvalidAnimation(format!(
+                    "frame {} has a different pixel format than the first frame",
+                    index
+                )));
+            }
+            if (image.width(), image.height()) != (frame_control.width(), frame_control.height()) {
+                return Err(Error::InvalidAnimation(format!(
+                    "frame {} is {}x{}, but its frame control says {}x{}",
+                    index,
+                    image.width(),
+                    image.height(),
+                    frame_control.width(),
+                    frame_control.height()
+                )));
+            }
+
+            let right = frame_control.x_offset() as u64 + frame_control.width() as u64;
+            let bottom = frame_control.y_offset() as u64 + frame_control.height() as u64;
+            if right > first.width() as u64 || bottom > first.height() as u64 {
+                return Err(Error::InvalidAnimation(format!(
+                    "frame {} lies outside the {}x{} canvas",
+                    index,
+                    first.width(),
+                    first.height()
+                )));
+            }
+            if index == 0 && (frame_control.x_offset(), frame_control.y_offset()) != (0, 0) {
+                return Err(invalid("the first frame must start at the canvas origin"));
+            }
+
+            let encoded = image.to_png(level)?;
+            let mut frame_control = frame_control.clone();
+            frame_control.set_sequence_number(sequence_number);
+            sequence_number += 1;
+
+            if index == 0 {
+                // IHDR and the palette chunks, then acTL and the default image
+                let (idat_chunks, header_chunks): (Vec<&Chunk>, Vec<&Chunk>) = encoded
+                    .chunks()
+                    .iter()
+                    .filter(|chunk| chunk.chunk_type().str() != "IEND")
+                    .partition(|chunk| chunk.chunk_type().str() == "IDAT");
+                chunks.extend(header_chunks.into_iter().cloned());
+                chunks.push(AnimationControl::new(frames.len() as u32, num_plays)?.to_chunk()?);
+                chunks.push(frame_control.to_chunk()?);
+                chunks.extend(idat_chunks.into_iter().cloned());
+                continue;
+            }
+
+            chunks.push(frame_control.to_chunk()?);
+            for idat in encoded
+                .chunks()
+                .iter()
+                .filter(|chunk| chunk.chunk_type().str() == "IDAT")
+            {
+                chunks.push(fdat_chunk(sequence_number, idat.data())?);
+                sequence_number += 1;
+            }
+        }
+
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new())?);
+        return Ok(Png::from_chunks(chunks));
+    }
+
+    pub fn control(&self) -> &AnimationControl {
+        return &self.m_control;
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        return &self.m_frames;
+    }
+}
+
+impl fmt::Display for Animation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.control())?;
+        for (index, frame) in self.frames().iter().enumerate() {
+            let default_image = if frame.is_default_image() {
+                " (default image)"
+            } else {
+                ""
+            };
+            writeln!(f, "Frame {}{}: {}", index, default_image, frame.control())?;
+        }
+        Ok(())
+    }
+}
+
+// standalone PNG of frame `index` as stored, without compositing it onto the previous frames
+pub(crate) fn extract_frame(png: &Png, index: usize) -> Result<Png> {
+    let animation = Animation::from_png(png)?;
+    let frame = animation
+        .frames()
+        .get(index)
+        .ok_or(Error::InvalidFrameIndex {
+            index,
+            len: animation.frames().len(),
+        })?;
+
+    let header = png.header_info()?;
+    let frame_header = ImageHeader::new(
+        frame.control().width(),
+        frame.control().height(),
+        header.bit_depth(),
+        header.color_type(),
+        header.is_interlaced(),
+    )?;
+
+    // ancillary chunks describing the pixels (PLTE, tRNS, gAMA, ...) come before the image data
+    let mut chunks: Vec<Chunk> = vec![frame_header.to_chunk()?];
+    chunks.extend(
+        png.chunks()
+            .iter()
+            .take_while(|chunk| chunk.chunk_type().str() != "IDAT")
+            .filter(|chunk| !IMAGE_DATA_CHUNKS.contains(&chunk.chunk_type().str()))
+            .cloned(),
+    );
+    chunks.push(Chunk::new(
+        ChunkType::from_str("IDAT")?,
+        frame.data().to_vec(),
+    )?);
+    chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new())?);
+
+    return Ok(Png::from_chunks(chunks));
+}
+
+// removes the frames at `indices` and renumbers the rest, returning how many frames were removed;
+// a dropped default image stays as the image shown by decoders without APNG support, but leaves
+// the animation
+pub(crate) fn drop_frames(png: &Png, indices: &[usize]) -> Result<(Png, usize)> {
+    let animation = Animation::from_png(png)?;
+    let len = animation.frames().len();
+    if let Some(index) = indices.iter().find(|index| **index >= len) {
+        return Err(Error::InvalidFrameIndex { index: *index, len });
+    }
+    let remaining = (0..len).filter(|index| !indices.contains(index)).count();
+    if remaining == 0 {
+        return Err(invalid("cannot drop every frame"));
+    }
+
+    let mut chunks: Vec<Chunk> = Vec::new();
+    let mut frame: Option<usize> = None;
+    let mut sequence_number: u32 = 0;
+    for chunk in png.chunks() {
+        let dropped = frame.is_some_and(|frame| indices.contains(&frame));
+        match chunk.chunk_type().str() {
+            AnimationControl::CHUNK_TYPE => {
+                let control =
+                    AnimationControl::new(remaining as u32, animation.control().num_plays())?;
+                chunks.push(control.to_chunk()?);
+            }
+            FrameControl::CHUNK_TYPE => {
+                let index = frame.map_or(0, |frame| frame + 1);
+                frame = Some(index);
+                if indices.contains(&index) {
+                    continue;
+                }
+
+                let mut frame_control = FrameControl::from_chunk(chunk)?;
+                frame_control.set_sequence_number(sequence_number);
+                sequence_number += 1;
+                chunks.push(frame_control.to_chunk()?);
+            }
+            "fdAT" if dropped => (),
+            "fdAT" => {
+                chunks.push(fdat_chunk(sequence_number, &chunk.data()[4..])?);
+                sequence_number += 1;
+            }
+            _ => chunks.push(chunk.clone()),
+        }
+    }
+
+    return Ok((Png::from_chunks(chunks), len - remaining));
+}
+
+fn fdat_chunk(sequence_number: u32, data: &[u8]) -> Result<Chunk> {
+    let mut fdat_data: Vec<u8> = Vec::with_capacity(4 + data.len());
+    fdat_data.extend_from_slice(&sequence_number.to_be_bytes());
+    fdat_data.extend_from_slice(data);
+
+    return Chunk::new(ChunkType::from_str("fdAT")?, fdat_data);
+}
+
+fn expect_chunk<'a>(chunk: &'a Chunk, chunk_type: &str, len: usize) -> Result<&'a [u8]> {
+    if chunk.chunk_type().str() != chunk_type {
+        return Err(Error::UnexpectedChunkType {
+            expected: String::from(chunk_type),
+            found: String::from(chunk.chunk_type().str()),
+        });
+    }
+
+    let data = chunk.data();
+    if data.len() != len {
+        return Err(malformed(
+            chunk_type,
+            format!("expected {} bytes of data, found {}", len, data.len()),
+        ));
+    }
+
+    return Ok(data);
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    return u32::from_be_bytes(data[offset..(offset + 4)].try_into().unwrap());
+}
+
+fn malformed(chunk_type: &str, reason: String) -> Error {
+    return Error::MalformedChunk {
+        chunk_type: String::from(chunk_type),
+        reason,
+    };
+}
+
+fn invalid(reason: &str) -> Error {
+    return Error::InvalidAnimation(String::from(reason));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_header::ColorType;
+    use crate::inflate::DEFAULT_OUTPUT_LIMIT;
+
+    fn frame(
+        width: u32,
+        height: u32,
+        x_offset: u32,
+        y_offset: u32,
+        seed: u8,
+    ) -> (Image, FrameControl) {
+        let len = (width * height * 3) as usize;
+        let pixels: Vec<u8> = (0..len).map(|i| (i as u8).wrapping_mul(seed)).collect();
+        let image = Image::new(width, height, ColorType::Rgb, 8, pixels).unwrap();
+        let mut frame_control = FrameControl::new(width, height, x_offset, y_offset).unwrap();
+        frame_control.set_delay(seed as u16, 10);
+        return (image, frame_control);
+    }
+
+    fn animation() -> (Vec<(Image, FrameControl)>, Png) {
+        let frames = vec![
+            frame(8, 6, 0, 0, 3),
+            frame(4, 4, 2, 1, 5),
+            frame(8, 6, 0, 0, 7),
+        ];
+        let png = Animation::build(&frames, 2, CompressionLevel::Fast).unwrap();
+        return (frames, png);
+    }
+
+    fn set_sequence_number(png: &mut Png, index: usize, sequence_number: u32) {
+        let chunk = &png.chunks()[index];
+        let mut data = chunk.data().to_vec();
+        data[0..4].copy_from_slice(&sequence_number.to_be_bytes());
+        let chunk = Chunk::new(chunk.chunk_type().clone(), data).unwrap();
+        let mut chunks = png.chunks().clone();
+        chunks[index] = chunk;
+        *png = Png::from_chunks(chunks);
+    }
+
+    #[test]
+    pub fn test_apng_controls() {
+        let control = AnimationControl::new(3, 0).unwrap();
+        assert_eq!(
+            AnimationControl::from_chunk(&control.to_chunk().unwrap()).unwrap(),
+            control
+        );
+        assert!(AnimationControl::new(0, 0).is_err());
+
+        let mut frame_control = FrameControl::new(10, 20, 1, 2).unwrap();
+        frame_control.set_delay(1, 0);
+        frame_control.set_dispose_op(DisposeOp::Previous);
+        frame_control.set_blend_op(BlendOp::Over);
+        let chunk = frame_control.to_chunk().unwrap();
+        assert_eq!(chunk.length(), 26);
+        assert_eq!(FrameControl::from_chunk(&chunk).unwrap(), frame_control);
+        assert_eq!(frame_control.delay_secs(), 0.01);
+
+        let mut data = chunk.data().to_vec();
+        data[24] = 3;
+        let chunk = Chunk::new(ChunkType::from_str("fcTL").unwrap(), data).unwrap();
+        assert!(FrameControl::from_chunk(&chunk).is_err());
+        assert!(FrameControl::from_chunk(&control.to_chunk().unwrap()).is_err());
+    }
+
+    #[test]
+    pub fn test_apng_build_and_parse() {
+        let (frames, png) = animation();
+        assert!(png.validate().is_empty());
+
+        let animation = Animation::from_png(&png).unwrap();
+        assert_eq!(animation.control().num_frames(), 3);
+        assert_eq!(animation.control().num_plays(), 2);
+        assert!(animation.frames()[0].is_default_image());
+        for (i, (frame, (_, frame_control))) in
+            animation.frames().iter().zip(frames.iter()).enumerate()
+        {
+            assert_eq!(frame.control().sequence_number() == 0, i == 0);
+            assert_eq!(frame.control().x_offset(), frame_control.x_offset());
+            assert_eq!(frame.control().delay_num(), frame_control.delay_num());
+        }
+
+        // fcTL 0, then fcTL and fdAT for each later frame
+        let sequence_numbers: Vec<u32> = png
+            .chunks()
+            .iter()
+            .filter(|chunk| ["fcTL", "fdAT"].contains(&chunk.chunk_type().str()))
+            .map(|chunk| read_u32(chunk.data(), 0))
+            .collect();
+        assert_eq!(sequence_numbers, vec![0, 1, 2, 3, 4]);
+
+        let mismatched = vec![frame(8, 6, 0, 0, 3), frame(8, 6, 4, 0, 5)];
+        assert!(Animation::build(&mismatched, 0, CompressionLevel::Fast).is_err());
+        assert!(Animation::build(&[], 0, CompressionLevel::Fast).is_err());
+    }
+
+    #[test]
+    pub fn test_apng_parse_errors() {
+        let (_, png) = animation();
+
+        let mut still = png.clone();
+        still.delete_chunk("acTL").unwrap();
+        assert!(matches!(
+            Animation::from_png(&still),
+            Err(Error::ChunkNotFound(_))
+        ));
+
+        // fdAT of frame 1 claims sequence number 5
+        let mut reordered = png.clone();
+        let fdat = reordered
+            .chunks()
+            .iter()
+            .position(|chunk| chunk.chunk_type().str() == "fdAT")
+            .unwrap();
+        set_sequence_number(&mut reordered, fdat, 5);
+        assert!(matches!(
+            Animation::from_png(&reordered),
+            Err(Error::InvalidSequenceNumber {
+                expected: 2,
+                found: 5
+            })
+        ));
+
+        let mut missing = png.clone();
+        let fdat = missing
+            .chunks()
+            .iter()
+            .rposition(|chunk| chunk.chunk_type().str() == "fdAT")
+            .unwrap();
+        let mut chunks = missing.chunks().clone();
+        chunks.remove(fdat);
+        missing = Png::from_chunks(chunks);
+        assert!(matches!(
+            Animation::from_png(&missing),
+            Err(Error::InvalidAnimation(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_apng_extract_frame() {
+        let (frames, png) = animation();
+        for (i, (image, _)) in frames.iter().enumerate() {
+            let extracted = png.extract_frame(i).unwrap();
+            assert!(extracted.validate().is_empty());
+            assert!(extracted.search_chunk("acTL").is_none());
+            assert_eq!(
+                &Image::from_png(&extracted, DEFAULT_OUTPUT_LIMIT).unwrap(),
+                image
+            );
+        }
+        assert!(matches!(
+            png.extract_frame(3),
+            Err(Error::InvalidFrameIndex { index: 3, len: 3 })
+        ));
+    }
+
+    #[test]
+    pub fn test_apng_drop_frames() {
+        let (frames, png) = animation();
+
+        let mut dropped = png.clone();
+        // a repeated index still drops a single frame
+        assert_eq!(dropped.drop_frames(&[1, 1]).unwrap(), 1);
+        let animation = dropped.animation().unwrap();
+        assert_eq!(animation.control().num_frames(), 2);
+        assert_eq!(animation.frames()[1].control().sequence_number(), 1);
+        assert_eq!(
+            Image::from_png(&dropped.extract_frame(1).unwrap(), DEFAULT_OUTPUT_LIMIT).unwrap(),
+            frames[2].0
+        );
+
+        // the default image stays in IDAT, but is no longer a frame
+        let mut dropped = png.clone();
+        dropped.drop_frames(&[0]).unwrap();
+        let animation = dropped.animation().unwrap();
+        assert_eq!(animation.frames().len(), 2);
+        assert!(!animation.frames()[0].is_default_image());
+        assert_eq!(animation.frames()[0].control().sequence_number(), 0);
+        assert_eq!(
+            Image::from_png(&dropped, DEFAULT_OUTPUT_LIMIT).unwrap(),
+            frames[0].0
+        );
+
+        let mut dropped = png.clone();
+        assert!(dropped.drop_frames(&[0, 1, 2]).is_err());
+        assert!(dropped.drop_frames(&[3]).is_err());
+        assert_eq!(dropped, png);
+    }
+}
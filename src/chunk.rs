@@ -1,9 +1,22 @@
 use std::fmt;
-use std::str::from_utf8;
+use std::str::{from_utf8, FromStr};
 
+use crate::chunk_ref::ChunkRef;
 use crate::chunk_type::ChunkType;
-
-use crc::crc32::checksum_ieee;
+use crate::encoding::Encoding;
+use crate::error::{Error, Result};
+use crate::json::Json;
+
+use crc::crc32::{checksum_ieee, update, IEEE_TABLE};
+
+// what parsing does with a stored crc that does not match the chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    // the chunk is rejected
+    Strict,
+    // the chunk keeps the stored crc, see `Chunk::has_valid_crc`
+    Lenient,
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
@@ -11,59 +24,89 @@ pub struct Chunk {
     m_chunk_type: ChunkType,
     m_data: Vec<u8>,
     m_crc: u32,
+    // known from when the crc was computed or checked, so it is never recomputed
+    m_crc_valid: bool,
 }
 
 impl Chunk {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        if bytes.len() < 12 {
-            return Err(String::from("[Chunk] Invalid byte length."));
+    pub const MAX_LENGTH: u32 = (1 << 31) - 1;
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (chunk, consumed) = Self::parse(bytes)?;
+        if consumed != bytes.len() {
+            return Err(Error::LengthMismatch {
+                declared: chunk.length(),
+                actual: bytes.len() - 12,
+            });
         }
 
-        let m_length = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+        return Ok(chunk);
+    }
 
-        let m_chunk_type = ChunkType::from_bytes(&bytes[4..8])?;
-        if !m_chunk_type.is_valid() {
-            return Err(String::from("[Chunk] Invalid ChunkType."));
-        }
+    // parses the chunk at the front of `bytes`, returning it with the number of bytes consumed
+    pub fn parse(bytes: &[u8]) -> Result<(Self, usize)> {
+        let (chunk_ref, consumed) = ChunkRef::parse(bytes)?;
+        return Ok((chunk_ref.to_chunk(), consumed));
+    }
 
-        let m_data = bytes[8..(bytes.len() - 4)].to_vec();
-        let m_crc = u32::from_be_bytes(bytes[(bytes.len() - 4)..(bytes.len())].try_into().unwrap());
-
-        let real_crc_bytes = &bytes[4..(bytes.len() - 4)];
-        let real_crc = checksum_ieee(real_crc_bytes);
-        if m_crc != real_crc {
-            return Err(String::from("[Chunk] Invalid CRC value."));
-        }
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Result<Self> {
+        let m_length = match u32::try_from(data.len()) {
+            Ok(length) if length <= Self::MAX_LENGTH => length,
+            _ => {
+                return Err(Error::InvalidLength {
+                    offset: 0,
+                    length: u32::try_from(data.len()).unwrap_or(u32::MAX),
+                })
+            }
+        };
+        let m_crc = Self::compute_crc(&chunk_type, &data);
 
         return Ok(Self {
             m_length,
-            m_chunk_type,
-            m_data,
+            m_chunk_type: chunk_type,
+            m_data: data,
             m_crc,
+            m_crc_valid: true,
         });
     }
 
-    pub fn from_str(chunk_type: &str, data: &str) -> Result<Self, String> {
-        let m_length = data.len() as u32;
+    pub fn from_str(chunk_type: &str, data: &str) -> Result<Self> {
         let m_chunk_type = ChunkType::from_str(chunk_type)?;
-        let m_data = data.as_bytes().to_vec();
+        return Self::new(m_chunk_type, data.as_bytes().to_vec());
+    }
 
-        let m_crc_bytes: Vec<u8> = m_chunk_type
-            .bytes()
-            .iter()
-            .chain(m_data.iter())
-            .copied()
-            .collect();
-        let m_crc = checksum_ieee(&m_crc_bytes);
+    // builds a chunk from already separated fields, verifying the stored crc in strict mode
+    pub(crate) fn from_parts(
+        chunk_type: ChunkType,
+        data: Vec<u8>,
+        crc: u32,
+        mode: ParseMode,
+    ) -> Result<Self> {
+        if !chunk_type.is_valid() {
+            return Err(Error::ReservedBitSet(chunk_type));
+        }
+
+        let real_crc = Self::compute_crc(&chunk_type, &data);
+        if mode == ParseMode::Strict && crc != real_crc {
+            return Err(Error::InvalidCrc {
+                expected: real_crc,
+                actual: crc,
+            });
+        }
 
         return Ok(Self {
-            m_length,
-            m_chunk_type,
-            m_data,
-            m_crc,
+            m_length: data.len() as u32,
+            m_chunk_type: chunk_type,
+            m_data: data,
+            m_crc: crc,
+            m_crc_valid: crc == real_crc,
         });
     }
 
+    pub(crate) fn compute_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        return update(checksum_ieee(chunk_type.bytes()), &IEEE_TABLE, data);
+    }
+
     pub fn length(&self) -> u32 {
         return self.m_length;
     }
@@ -76,14 +119,27 @@ impl Chunk {
         return &self.m_data;
     }
 
-    pub fn data_str(&self) -> &str {
-        return from_utf8(self.data()).unwrap();
+    pub fn data_str(&self) -> Result<&str> {
+        return from_utf8(self.data()).map_err(Error::InvalidUtf8);
     }
 
     pub fn crc(&self) -> u32 {
         return self.m_crc;
     }
 
+    // whether the stored crc matches the type and data
+    pub fn has_valid_crc(&self) -> bool {
+        return self.m_crc_valid;
+    }
+
+    // the same chunk with the crc recomputed from its type and data
+    pub fn with_valid_crc(&self) -> Self {
+        let mut chunk = self.clone();
+        chunk.m_crc = Self::compute_crc(self.chunk_type(), self.data());
+        chunk.m_crc_valid = true;
+        return chunk;
+    }
+
     pub fn bytes(&self) -> Vec<u8> {
         let length_bytes = u32::to_be_bytes(self.length());
         let chunk_type_bytes = self.chunk_type().bytes();
@@ -100,6 +156,45 @@ impl Chunk {
 
         return bytes;
     }
+
+    // type, property bits, length and CRC, with the data too if given an encoding for it
+    pub fn to_json(&self, data_encoding: Option<Encoding>) -> Json {
+        let chunk_type = self.chunk_type();
+        let mut fields = vec![
+            ("type", Json::string(chunk_type.str())),
+            ("critical", Json::Bool(chunk_type.is_critical())),
+            ("public", Json::Bool(chunk_type.is_public())),
+            (
+                "reserved_bit_valid",
+                Json::Bool(chunk_type.is_reserved_bit_valid()),
+            ),
+            ("safe_to_copy", Json::Bool(chunk_type.is_safe_to_copy())),
+            ("length", Json::Number(self.length() as u64)),
+            ("crc", Json::Number(self.crc() as u64)),
+            ("crc_valid", Json::Bool(self.has_valid_crc())),
+        ];
+        if let Some(encoding) = data_encoding {
+            // utf8 falls back to replacement characters for binary data
+            let data = encoding
+                .encode(self.data())
+                .unwrap_or_else(|_| String::from_utf8_lossy(self.data()).into_owned());
+            fields.push(("data", Json::String(data)));
+        }
+
+        return Json::object(fields);
+    }
+}
+
+impl From<ChunkRef<'_>> for Chunk {
+    fn from(chunk_ref: ChunkRef<'_>) -> Self {
+        return Self {
+            m_length: chunk_ref.length(),
+            m_chunk_type: chunk_ref.chunk_type().clone(),
+            m_data: chunk_ref.data().to_vec(),
+            m_crc: chunk_ref.crc(),
+            m_crc_valid: chunk_ref.has_valid_crc(),
+        };
+    }
 }
 
 impl fmt::Display for Chunk {
@@ -139,13 +234,43 @@ mod tests {
         assert_eq!(chunk.length(), 42);
         assert_eq!(chunk.chunk_type().str(), "RuSt");
         assert_eq!(
-            chunk.data_str(),
+            chunk.data_str().unwrap(),
             "This is where your secret message will be!"
         );
         assert_eq!(chunk.crc(), 2882656334);
         assert_eq!(chunk.bytes(), bytes);
     }
 
+    #[test]
+    pub fn test_chunk_parse() {
+        let chunk = Chunk::from_str("RuSt", "This is where your secret message will be!").unwrap();
+        let mut bytes = chunk.bytes();
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        let (parsed, consumed) = Chunk::parse(&bytes).unwrap();
+        assert_eq!(parsed, chunk);
+        assert_eq!(consumed, 54);
+
+        let chunk_res = Chunk::from_bytes(&bytes);
+        assert!(matches!(
+            chunk_res,
+            Err(Error::LengthMismatch {
+                declared: 42,
+                actual: 45
+            })
+        ));
+
+        let chunk_res = Chunk::parse(&bytes[..50]);
+        assert!(matches!(
+            chunk_res,
+            Err(Error::TruncatedChunk {
+                needed: 54,
+                available: 50,
+                ..
+            })
+        ));
+    }
+
     #[test]
     pub fn test_chunk_from_str() {
         let chunk_type_str = "RuSt";
@@ -156,12 +281,23 @@ mod tests {
         assert_eq!(chunk.length(), 42);
         assert_eq!(chunk.chunk_type().str(), "RuSt");
         assert_eq!(
-            chunk.data_str(),
+            chunk.data_str().unwrap(),
             "This is where your secret message will be!"
         );
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    pub fn test_chunk_new_binary() {
+        let data: Vec<u8> = vec![0, 159, 146, 150, 255];
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), data.clone()).unwrap();
+
+        assert_eq!(chunk.length(), 5);
+        assert_eq!(chunk.data(), &data[..]);
+        assert!(matches!(chunk.data_str(), Err(Error::InvalidUtf8(_))));
+        assert_eq!(Chunk::from_bytes(&chunk.bytes()).unwrap(), chunk);
+    }
+
     #[test]
     pub fn test_chunk_validation() {
         let length_bytes = u32::to_be_bytes(42);
@@ -180,7 +316,23 @@ mod tests {
             .collect();
 
         let chunk_res = Chunk::from_bytes(&bytes);
-        assert!(chunk_res.is_err());
+        assert!(matches!(
+            chunk_res,
+            Err(Error::InvalidCrc {
+                expected: 2882656334,
+                actual: 2882656333
+            })
+        ));
+    }
+
+    #[test]
+    pub fn test_chunk_reserved_bit() {
+        let chunk = Chunk::from_str("Rust", "reserved").unwrap();
+        let chunk_res = Chunk::from_bytes(&chunk.bytes());
+        assert!(matches!(chunk_res, Err(Error::ReservedBitSet(_))));
+
+        let chunk_res = Chunk::from_bytes(&[0, 0, 0, 0]);
+        assert!(matches!(chunk_res, Err(Error::TruncatedChunk { .. })));
     }
 
     #[test]
@@ -0,0 +1,234 @@
+use std::str::from_utf8;
+
+use crate::chunk::{Chunk, ParseMode};
+use crate::chunk_type::ChunkType;
+use crate::error::{Error, Result};
+use crate::png::Png;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkRef<'a> {
+    m_offset: usize,
+    m_chunk_type: ChunkType,
+    m_data: &'a [u8],
+    m_crc: u32,
+    m_crc_valid: bool,
+}
+
+impl<'a> ChunkRef<'a> {
+    // parses the chunk at the front of `bytes` without copying its data
+    pub fn parse(bytes: &'a [u8]) -> Result<(Self, usize)> {
+        return Self::parse_with_mode(bytes, ParseMode::Strict);
+    }
+
+    pub fn parse_with_mode(bytes: &'a [u8], mode: ParseMode) -> Result<(Self, usize)> {
+        if bytes.len() < 12 {
+            return Err(Error::TruncatedChunk {
+                offset: 0,
+                needed: 12,
+                available: bytes.len(),
+            });
+        }
+
+        let length = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+        if length > Chunk::MAX_LENGTH {
+            return Err(Error::InvalidLength { offset: 0, length });
+        }
+
+        // length(4 bytes) + chunk_type(4 bytes) + chunk_data(length bytes) + crc(4 bytes)
+        let total_len = match (length as usize).checked_add(12) {
+            Some(total_len) => total_len,
+            None => return Err(Error::InvalidLength { offset: 0, length }),
+        };
+        if bytes.len() < total_len {
+            return Err(Error::TruncatedChunk {
+                offset: 0,
+                needed: total_len,
+                available: bytes.len(),
+            });
+        }
+
+        let m_chunk_type = ChunkType::from_bytes(&bytes[4..8])?;
+        if !m_chunk_type.is_valid() {
+            return Err(Error::ReservedBitSet(m_chunk_type));
+        }
+
+        let m_data = &bytes[8..(total_len - 4)];
+        let m_crc = u32::from_be_bytes(bytes[(total_len - 4)..total_len].try_into().unwrap());
+
+        let real_crc = Chunk::compute_crc(&m_chunk_type, m_data);
+        if mode == ParseMode::Strict && m_crc != real_crc {
+            return Err(Error::InvalidCrc {
+                expected: real_crc,
+                actual: m_crc,
+            });
+        }
+
+        let chunk_ref = Self {
+            m_offset: 0,
+            m_chunk_type,
+            m_data,
+            m_crc,
+            m_crc_valid: m_crc == real_crc,
+        };
+        return Ok((chunk_ref, total_len));
+    }
+
+    // offset of the chunk's length field within the buffer it was parsed from
+    pub fn offset(&self) -> usize {
+        return self.m_offset;
+    }
+
+    pub fn length(&self) -> u32 {
+        return self.m_data.len() as u32;
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        return &self.m_chunk_type;
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        return self.m_data;
+    }
+
+    pub fn data_str(&self) -> Result<&'a str> {
+        return from_utf8(self.m_data).map_err(Error::InvalidUtf8);
+    }
+
+    pub fn crc(&self) -> u32 {
+        return self.m_crc;
+    }
+
+    // whether the stored crc matches the type and data, as checked when parsing
+    pub fn has_valid_crc(&self) -> bool {
+        return self.m_crc_valid;
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        return Chunk::from(self.clone());
+    }
+}
+
+pub struct ChunkRefs<'a> {
+    m_bytes: &'a [u8],
+    m_offset: usize,
+    m_mode: ParseMode,
+    m_done: bool,
+}
+
+impl<'a> ChunkRefs<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        return Self::with_mode(bytes, ParseMode::Strict);
+    }
+
+    pub fn with_mode(bytes: &'a [u8], mode: ParseMode) -> Result<Self> {
+        if bytes.len() < 8 || bytes[0..8] != Png::STANDARD_HEADER {
+            return Err(Error::InvalidSignature);
+        }
+
+        return Ok(Self {
+            m_bytes: bytes,
+            m_offset: 8,
+            m_mode: mode,
+            m_done: false,
+        });
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.m_done || self.m_offset >= self.m_bytes.len() {
+            return None;
+        }
+
+        let offset = self.m_offset;
+        match ChunkRef::parse_with_mode(&self.m_bytes[offset..], self.m_mode) {
+            Ok((mut chunk_ref, consumed)) => {
+                chunk_ref.m_offset = offset;
+                self.m_offset += consumed;
+                return Some(Ok(chunk_ref));
+            }
+            Err(err) => {
+                self.m_done = true;
+                return Some(Err(err.at_offset(offset)));
+            }
+        }
+    }
+}
+
+// three chunks behind the signature, shared by the tests of both parsers
+#[cfg(test)]
+pub(crate) fn test_png_bytes() -> Vec<u8> {
+    let chunks = vec![
+        Chunk::from_str("FrSt", "I am the first chunk").unwrap(),
+        Chunk::from_str("miDl", "I am another chunk").unwrap(),
+        Chunk::from_str("LASt", "I am the last chunk").unwrap(),
+    ];
+    return Png::from_chunks(chunks).bytes();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_chunk_refs() {
+        let bytes = test_png_bytes();
+        let chunk_refs: Vec<ChunkRef> = ChunkRefs::new(&bytes)
+            .unwrap()
+            .collect::<Result<Vec<ChunkRef>>>()
+            .unwrap();
+
+        assert_eq!(chunk_refs.len(), 3);
+        assert_eq!(chunk_refs[1].offset(), 40);
+        assert_eq!(chunk_refs[1].chunk_type().str(), "miDl");
+        assert_eq!(chunk_refs[1].data_str().unwrap(), "I am another chunk");
+        assert_eq!(chunk_refs[1].length(), 18);
+
+        // the data is borrowed straight from the input buffer
+        assert_eq!(chunk_refs[1].data().as_ptr(), bytes[48..].as_ptr());
+    }
+
+    #[test]
+    pub fn test_chunk_ref_to_chunk() {
+        let bytes = test_png_bytes();
+        let chunks: Vec<Chunk> = ChunkRefs::new(&bytes)
+            .unwrap()
+            .map(|chunk_ref| return chunk_ref.map(|chunk_ref| chunk_ref.to_chunk()))
+            .collect::<Result<Vec<Chunk>>>()
+            .unwrap();
+        assert_eq!(chunks, Png::from_bytes(&bytes).unwrap().chunks().clone());
+    }
+
+    #[test]
+    pub fn test_chunk_refs_errors() {
+        let mut bytes = test_png_bytes();
+        assert!(matches!(
+            ChunkRefs::new(&bytes[..7]),
+            Err(Error::InvalidSignature)
+        ));
+
+        bytes[45] ^= 1;
+        let mut chunk_refs = ChunkRefs::new(&bytes).unwrap();
+        assert!(chunk_refs.next().unwrap().is_ok());
+        assert!(matches!(
+            chunk_refs.next(),
+            Some(Err(Error::InvalidCrc { .. }))
+        ));
+        assert!(chunk_refs.next().is_none());
+
+        let chunk_refs = ChunkRefs::with_mode(&bytes, ParseMode::Lenient).unwrap();
+        let crcs_valid: Vec<bool> = chunk_refs
+            .map(|chunk_ref| chunk_ref.unwrap().to_chunk().has_valid_crc())
+            .collect();
+        assert_eq!(crcs_valid, vec![true, false, true]);
+
+        let mut chunk_refs = ChunkRefs::new(&bytes[..50]).unwrap();
+        assert!(chunk_refs.next().unwrap().is_ok());
+        assert!(matches!(
+            chunk_refs.next(),
+            Some(Err(Error::TruncatedChunk { offset: 40, .. }))
+        ));
+    }
+}
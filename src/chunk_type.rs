@@ -1,5 +1,7 @@
 use std::fmt;
-use std::str::from_utf8;
+use std::str::{from_utf8, FromStr};
+
+use crate::error::{Error, Result};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChunkType {
@@ -7,23 +9,15 @@ pub struct ChunkType {
 }
 
 impl ChunkType {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        if bytes.len() != 4 {
-            return Err(String::from("[ChunkType] Invalid byte length."));
-        }
-
-        if !Self::is_bytes_valid(bytes) {
-            return Err(String::from("[ChunkType] Invalid byte values."));
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != 4 || !Self::is_bytes_valid(bytes) {
+            return Err(Error::InvalidChunkType(bytes.to_vec()));
         }
 
         let m_bytes: [u8; 4] = bytes.try_into().unwrap();
         return Ok(Self { m_bytes });
     }
 
-    pub fn from_str(s: &str) -> Result<Self, String> {
-        return Self::from_bytes(s.as_bytes());
-    }
-
     pub fn bytes(&self) -> &[u8] {
         return &self.m_bytes;
     }
@@ -63,6 +57,14 @@ impl ChunkType {
     }
 }
 
+impl FromStr for ChunkType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        return Self::from_bytes(s.as_bytes());
+    }
+}
+
 impl fmt::Display for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "ChunkType: {{ Bytes: {:?}}}", self.bytes())
@@ -132,7 +134,10 @@ mod tests {
         assert!(!chunk_type.is_valid());
 
         let chunk_type_res = ChunkType::from_str("Ru1t");
-        assert!(chunk_type_res.is_err());
+        assert!(matches!(chunk_type_res, Err(Error::InvalidChunkType(_))));
+
+        let chunk_type_res = ChunkType::from_bytes(&[82, 117, 83]);
+        assert!(matches!(chunk_type_res, Err(Error::InvalidChunkType(_))));
     }
 
     #[test]
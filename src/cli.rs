@@ -0,0 +1,678 @@
+use std::str::FromStr;
+
+use pngme::{Error, Result};
+
+// an option of a command, taking a value if `value` names one
+pub struct Opt {
+    pub name: &'static str,
+    pub short: Option<char>,
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub struct Command {
+    // subcommands of a group are named e.g. "text set"
+    pub name: &'static str,
+    pub args: &'static str,
+    pub about: &'static str,
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    pub options: &'static [Opt],
+}
+
+const HELP: Opt = Opt {
+    name: "help",
+    short: Some('h'),
+    value: None,
+    help: "Print help",
+};
+const VERBOSE: Opt = Opt {
+    name: "verbose",
+    short: Some('v'),
+    value: None,
+    help: "Report what is done on stderr",
+};
+// accepted by every command
+const COMMON_OPTIONS: [Opt; 2] = [HELP, VERBOSE];
+
+const OUTPUT: Opt = Opt {
+    name: "output",
+    short: Some('o'),
+    value: Some("<file>"),
+    help: "Write the result to <file> instead of modifying the source file",
+};
+const MODE: Opt = Opt {
+    name: "mode",
+    short: None,
+    value: Some("<mode>"),
+    help: "Where the message is hidden: chunk (default), lsb or scatter",
+};
+const CHANNELS: Opt = Opt {
+    name: "channels",
+    short: None,
+    value: Some("<channels>"),
+    help: "Samples used by the pixel modes, from r, g, b, a and y",
+};
+const BITS: Opt = Opt {
+    name: "bits",
+    short: None,
+    value: Some("<n>"),
+    help: "Low bits used in every sample by the pixel modes, from 1 (default) to 8",
+};
+const CONVERT: Opt = Opt {
+    name: "convert",
+    short: None,
+    value: None,
+    help: "Convert palette and sub-byte images to 8-bit truecolor for the pixel modes",
+};
+const ENCODING: Opt = Opt {
+    name: "encoding",
+    short: Some('e'),
+    value: Some("<encoding>"),
+    help: "Text encoding of the message: utf8 (default), hex or base64",
+};
+const OCCURRENCE: Opt = Opt {
+    name: "occurrence",
+    short: None,
+    value: Some("<n>"),
+    help: "Use the n-th chunk of the type, counting from 0, instead of the first one",
+};
+const ALL: Opt = Opt {
+    name: "all",
+    short: None,
+    value: None,
+    help: "Use every chunk of the type",
+};
+const LENIENT: Opt = Opt {
+    name: "lenient",
+    short: None,
+    value: None,
+    help: "Read chunks with a bad CRC instead of rejecting the file",
+};
+const BACKUP: Opt = Opt {
+    name: "backup",
+    short: None,
+    value: Some("<suffix>"),
+    help: "Keep the replaced file as <file><suffix>, e.g. `--backup .bak`",
+};
+const DRY_RUN: Opt = Opt {
+    name: "dry-run",
+    short: Some('n'),
+    value: None,
+    help: "Report which chunks would change without writing anything",
+};
+
+pub const COMMANDS: [Command; 17] = [
+    Command {
+        name: "encode",
+        args: "<src_file> <dst_file> [<chunk_type>] [<message>]",
+        about: "Hide a message in a PNG file. The pixel modes take no chunk type.",
+        min_args: 2,
+        max_args: Some(4),
+        options: &[
+            Opt {
+                name: "file",
+                short: Some('f'),
+                value: Some("<msg_file>"),
+                help: "Read the message from <msg_file> (`-` for stdin)",
+            },
+            ENCODING,
+            Opt {
+                name: "encrypt",
+                short: None,
+                value: None,
+                help: "Encrypt the message with the passphrase in PNGME_PASSPHRASE",
+            },
+            Opt {
+                name: "position",
+                short: Some('p'),
+                value: Some("<position>"),
+                help: "Where the message chunk goes: end, before-iend (default), after-ihdr, \
+                       before-idat, index:<n>, before:<type> or after:<type>",
+            },
+            MODE,
+            CHANNELS,
+            BITS,
+            CONVERT,
+            BACKUP,
+            DRY_RUN,
+        ],
+    },
+    Command {
+        name: "decode",
+        args: "<src_file> [<chunk_type>]",
+        about: "Print a message hidden in a PNG file. The pixel modes take no chunk type.",
+        min_args: 1,
+        max_args: Some(2),
+        options: &[
+            Opt {
+                name: "output",
+                short: Some('o'),
+                value: Some("<msg_file>"),
+                help: "Write the message to <msg_file> (`-` for stdout) instead of printing it",
+            },
+            ENCODING,
+            OCCURRENCE,
+            ALL,
+            MODE,
+            CHANNELS,
+            BITS,
+        ],
+    },
+    Command {
+        name: "delete",
+        args: "<src_file> <chunk_type>",
+        about: "Delete a chunk from a PNG file.",
+        min_args: 2,
+        max_args: Some(2),
+        options: &[OCCURRENCE, ALL, OUTPUT, BACKUP, DRY_RUN],
+    },
+    Command {
+        name: "print",
+        args: "<src_file>...",
+        about: "Print the chunks of PNG files.",
+        min_args: 1,
+        max_args: None,
+        options: &[
+            Opt {
+                name: "format",
+                short: None,
+                value: Some("<format>"),
+                help: "Output format: text (default), table, json, or ndjson for one line per file",
+            },
+            Opt {
+                name: "data",
+                short: None,
+                value: Some("<encoding>"),
+                help: "Include the chunk data in the JSON output, as hex, base64 or utf8",
+            },
+            LENIENT,
+        ],
+    },
+    Command {
+        name: "dump",
+        args: "<src_file> <chunk>",
+        about: "Show the data of a chunk, given by type or by index, as a hexdump.",
+        min_args: 2,
+        max_args: Some(2),
+        options: &[
+            OCCURRENCE,
+            Opt {
+                name: "offset",
+                short: None,
+                value: Some("<n>"),
+                help: "Start at byte n of the chunk data",
+            },
+            Opt {
+                name: "length",
+                short: None,
+                value: Some("<n>"),
+                help: "Show at most n bytes (default 256)",
+            },
+            Opt {
+                name: "full",
+                short: None,
+                value: None,
+                help: "Show the whole chunk data",
+            },
+            LENIENT,
+        ],
+    },
+    Command {
+        name: "check",
+        args: "<src_file>",
+        about: "Check the chunk layout of a PNG file against the PNG spec.",
+        min_args: 1,
+        max_args: Some(1),
+        options: &[LENIENT],
+    },
+    Command {
+        name: "repair",
+        args: "<src_file>",
+        about: "Recompute the bad CRCs of a PNG file.",
+        min_args: 1,
+        max_args: Some(1),
+        options: &[
+            Opt {
+                name: "force",
+                short: None,
+                value: None,
+                help: "Also repair critical chunks, whose data may be damaged",
+            },
+            OUTPUT,
+            BACKUP,
+            DRY_RUN,
+        ],
+    },
+    Command {
+        name: "capacity",
+        args: "<src_file>",
+        about: "Print how many bytes the pixel modes can hide in a PNG file.",
+        min_args: 1,
+        max_args: Some(1),
+        options: &[CHANNELS, BITS, CONVERT],
+    },
+    Command {
+        name: "generate",
+        args: "<dst_file> <width> <height>",
+        about: "Write a <width>x<height> PNG file of random pixels.",
+        min_args: 3,
+        max_args: Some(3),
+        options: &[BACKUP, DRY_RUN],
+    },
+    Command {
+        name: "text list",
+        args: "<src_file>",
+        about: "List the tEXt, zTXt and iTXt entries of a PNG file.",
+        min_args: 1,
+        max_args: Some(1),
+        options: &[],
+    },
+    Command {
+        name: "text get",
+        args: "<src_file> <keyword>",
+        about: "Print the text of an entry.",
+        min_args: 2,
+        max_args: Some(2),
+        options: &[],
+    },
+    Command {
+        name: "text set",
+        args: "<src_file> <keyword> <text>",
+        about: "Add an entry, replacing the ones with the same keyword.",
+        min_args: 3,
+        max_args: Some(3),
+        options: &[
+            Opt {
+                name: "compress",
+                short: None,
+                value: None,
+                help: "Write a zTXt chunk, or a compressed iTXt chunk",
+            },
+            Opt {
+                name: "international",
+                short: None,
+                value: None,
+                help: "Write an iTXt chunk",
+            },
+            Opt {
+                name: "lang",
+                short: None,
+                value: Some("<tag>"),
+                help: "Language tag of an iTXt chunk",
+            },
+            Opt {
+                name: "translated",
+                short: None,
+                value: Some("<keyword>"),
+                help: "Translated keyword of an iTXt chunk",
+            },
+            OUTPUT,
+            BACKUP,
+            DRY_RUN,
+        ],
+    },
+    Command {
+        name: "text remove",
+        args: "<src_file> <keyword>",
+        about: "Remove every entry with the keyword.",
+        min_args: 2,
+        max_args: Some(2),
+        options: &[OUTPUT, BACKUP, DRY_RUN],
+    },
+    Command {
+        name: "apng list",
+        args: "<src_file>",
+        about: "List the frames of an animated PNG file.",
+        min_args: 1,
+        max_args: Some(1),
+        options: &[],
+    },
+    Command {
+        name: "apng extract",
+        args: "<src_file> <index> <dst_file>",
+        about: "Write a frame as a standalone PNG file.",
+        min_args: 3,
+        max_args: Some(3),
+        options: &[BACKUP, DRY_RUN],
+    },
+    Command {
+        name: "apng drop",
+        args: "<src_file> <index>...",
+        about: "Remove frames from an animated PNG file.",
+        min_args: 2,
+        max_args: None,
+        options: &[OUTPUT, BACKUP, DRY_RUN],
+    },
+    Command {
+        name: "apng build",
+        args: "<dst_file> <frame_file>...",
+        about: "Make an animated PNG file from still images sharing a pixel format.",
+        min_args: 2,
+        max_args: None,
+        options: &[
+            Opt {
+                name: "delay",
+                short: None,
+                value: Some("<ms>"),
+                help: "How long every frame is shown, 100 milliseconds by default",
+            },
+            Opt {
+                name: "plays",
+                short: None,
+                value: Some("<n>"),
+                help: "How many times the animation is played, 0 (forever) by default",
+            },
+            BACKUP,
+            DRY_RUN,
+        ],
+    },
+];
+
+const EXIT_STATUS: &str = "Exit status: 0 on success, 1 on failure, 2 for invalid usage, \
+                           3 if something is not found, 4 for corrupt input, 5 for I/O errors.";
+
+impl Command {
+    // the command named by the first one or two words of `args`
+    pub fn find(args: &[String]) -> Option<&'static Command> {
+        let first = args.first()?;
+        if let Some(second) = args.get(1) {
+            let name = format!("{} {}", first, second);
+            if let Some(command) = COMMANDS.iter().find(|command| command.name == name) {
+                return Some(command);
+            }
+        }
+
+        return COMMANDS.iter().find(|command| command.name == first);
+    }
+
+    pub fn usage(&self) -> String {
+        return format!("Usage: pngme {} {} [options]", self.name, self.args);
+    }
+
+    pub fn help(&self) -> String {
+        let mut help = format!("{}\n\n{}\n\nOptions:\n", self.about, self.usage());
+        for opt in self.options.iter().chain(COMMON_OPTIONS.iter()) {
+            let short = match opt.short {
+                Some(short) => format!("-{}, ", short),
+                None => String::new(),
+            };
+            let spec = format!("{}--{} {}", short, opt.name, opt.value.unwrap_or(""));
+            help.push_str(&format!("  {:<28} {}\n", spec.trim_end(), opt.help));
+        }
+
+        return help;
+    }
+
+    fn option(&self, arg: &str) -> Option<&'static Opt> {
+        let mut options = self.options.iter().chain(COMMON_OPTIONS.iter());
+        match arg.strip_prefix("--") {
+            Some(name) => return options.find(|opt| opt.name == name),
+            None => return options.find(|opt| arg.len() == 2 && opt.short == arg.chars().nth(1)),
+        }
+    }
+}
+
+// list of the commands, or of the subcommands of `group`
+pub fn overview(group: Option<&str>) -> String {
+    let mut help = match group {
+        Some(group) => format!(
+            "Usage: pngme {} <command> [<args>] [options]\n\nCommands:\n",
+            group
+        ),
+        None => String::from(
+            "PNGme: hide secret messages in PNG files\n\n\
+             Usage: pngme <command> [<args>] [options]\n\nCommands:\n",
+        ),
+    };
+    for command in COMMANDS.iter() {
+        if group.is_none_or(|group| command.name.split(' ').next() == Some(group)) {
+            help.push_str(&format!("  {:<14} {}\n", command.name, command.about));
+        }
+    }
+    help.push_str("\nRun `pngme <command> --help` for the options of a command.\n\n");
+    help.push_str(EXIT_STATUS);
+
+    return help;
+}
+
+pub enum Invocation {
+    Help(String),
+    Run(Matches),
+}
+
+pub struct Matches {
+    m_command: &'static Command,
+    m_positional: Vec<String>,
+    m_options: Vec<(&'static str, Option<String>)>,
+}
+
+impl Matches {
+    pub fn command(&self) -> &'static Command {
+        return self.m_command;
+    }
+
+    pub fn positional(&self) -> &[String] {
+        return &self.m_positional;
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        return self.m_options.iter().any(|(opt, _)| *opt == name);
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        return self
+            .m_options
+            .iter()
+            .rev()
+            .find(|(opt, _)| *opt == name)
+            .and_then(|(_, value)| value.as_deref());
+    }
+
+    pub fn parse_value<T: FromStr>(&self, name: &str) -> Result<Option<T>> {
+        match self.value(name) {
+            Some(value) => match value.parse() {
+                Ok(value) => return Ok(Some(value)),
+                Err(_) => {
+                    return Err(Error::InvalidUsage(format!(
+                        "Invalid value {:?} for --{}",
+                        value, name
+                    )))
+                }
+            },
+            None => return Ok(None),
+        }
+    }
+
+    pub fn is_verbose(&self) -> bool {
+        return self.flag(VERBOSE.name);
+    }
+}
+
+// `args` without the program name
+pub fn parse(args: &[String]) -> Result<Invocation> {
+    let wants_help = |arg: &String| arg == "--help" || arg == "-h";
+    match args.first().map(String::as_str) {
+        None | Some("--help") | Some("-h") => return Ok(Invocation::Help(overview(None))),
+        Some("help") if args.len() > 1 => {
+            return parse(&[&args[1..], &[String::from("--help")]].concat())
+        }
+        Some("help") => return Ok(Invocation::Help(overview(None))),
+        _ => (),
+    }
+
+    let command = match Command::find(args) {
+        Some(command) => command,
+        None if ["text", "apng"].contains(&args[0].as_str()) => {
+            if args.len() == 1 || args[1..].iter().any(wants_help) {
+                return Ok(Invocation::Help(overview(Some(&args[0]))));
+            }
+            return Err(Error::InvalidUsage(format!(
+                "Unknown command \"{} {}\"",
+                args[0], args[1]
+            )));
+        }
+        None => {
+            return Err(Error::InvalidUsage(format!(
+                "Unknown command {:?}",
+                args[0]
+            )))
+        }
+    };
+
+    let mut matches = Matches {
+        m_command: command,
+        m_positional: Vec::new(),
+        m_options: Vec::new(),
+    };
+    let mut rest = args[command.name.split(' ').count()..].iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--" {
+            matches.m_positional.extend(rest.by_ref().cloned());
+            break;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            matches.m_positional.push(arg.clone());
+            continue;
+        }
+
+        // `--name=value` or `--name value`
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let opt = command.option(name).ok_or(Error::InvalidUsage(format!(
+            "Unknown option {} for {}",
+            name, command.name
+        )))?;
+        let value = match (opt.value, inline_value) {
+            (Some(_), Some(value)) => Some(value),
+            (Some(value_name), None) => match rest.next() {
+                Some(value) => Some(value.clone()),
+                None => {
+                    return Err(Error::InvalidUsage(format!(
+                        "Option --{} needs a value {}",
+                        opt.name, value_name
+                    )))
+                }
+            },
+            (None, Some(_)) => {
+                return Err(Error::InvalidUsage(format!(
+                    "Option --{} takes no value",
+                    opt.name
+                )))
+            }
+            (None, None) => None,
+        };
+        matches.m_options.push((opt.name, value));
+    }
+
+    if matches.flag(HELP.name) {
+        return Ok(Invocation::Help(command.help()));
+    }
+    let count = matches.m_positional.len();
+    if count < command.min_args || command.max_args.is_some_and(|max| count > max) {
+        return Err(Error::InvalidUsage(format!(
+            "Wrong number of arguments for {}",
+            command.name
+        )));
+    }
+
+    return Ok(Invocation::Run(matches));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        return line.split_whitespace().map(String::from).collect();
+    }
+
+    fn run(line: &str) -> Matches {
+        match parse(&args(line)).unwrap() {
+            Invocation::Run(matches) => return matches,
+            Invocation::Help(_) => panic!("{:?} asks for help", line),
+        }
+    }
+
+    #[test]
+    pub fn test_cli_parse() {
+        let matches = run("encode a.png b.png ruSt hello --encrypt --mode=lsb -v --bits 2");
+        assert_eq!(matches.command().name, "encode");
+        assert_eq!(matches.positional(), &args("a.png b.png ruSt hello")[..]);
+        assert!(matches.flag("encrypt") && matches.is_verbose());
+        assert_eq!(matches.value("mode"), Some("lsb"));
+        assert_eq!(matches.parse_value::<u8>("bits").unwrap(), Some(2));
+        assert_eq!(matches.parse_value::<u8>("channels").unwrap(), None);
+
+        let matches = run("text set a.png Title -- --not-an-option");
+        assert_eq!(matches.command().name, "text set");
+        assert_eq!(matches.positional()[2], "--not-an-option");
+
+        let matches = run("apng drop a.png 0 2 -o b.png");
+        assert_eq!(matches.positional(), &args("a.png 0 2")[..]);
+        assert_eq!(matches.value("output"), Some("b.png"));
+    }
+
+    #[test]
+    pub fn test_cli_help() {
+        for line in [
+            "",
+            "--help",
+            "help",
+            "encode --help",
+            "help text set",
+            "text",
+            "apng -h",
+        ] {
+            assert!(matches!(parse(&args(line)).unwrap(), Invocation::Help(_)));
+        }
+        let help = COMMANDS[0].help();
+        assert!(help.contains("--position <position>"));
+        assert!(help.contains("-v, --verbose"));
+
+        // width before height, as in IHDR
+        let generate = Command::find(&args("generate a.png 4 3")).unwrap();
+        assert!(generate.usage().contains("<dst_file> <width> <height>"));
+    }
+
+    #[test]
+    pub fn test_cli_errors() {
+        for line in [
+            "frobnicate a.png",
+            "text frobnicate a.png",
+            "print a.png --mode lsb",
+            "check a.png b.png",
+            "decode",
+            "encode a.png b.png --bits",
+            "encode a.png b.png --encrypt=yes",
+        ] {
+            let err = parse(&args(line)).err().unwrap();
+            assert!(matches!(err, Error::InvalidUsage(_)), "{:?}", line);
+        }
+
+        let matches = run("capacity a.png --bits x");
+        assert!(matches.parse_value::<u8>("bits").is_err());
+    }
+
+    #[test]
+    pub fn test_cli_short_options() {
+        // a short option means the same long option in every command
+        let options = COMMANDS
+            .iter()
+            .flat_map(|command| command.options.iter())
+            .chain(COMMON_OPTIONS.iter());
+        let mut longs: Vec<(char, &str)> = Vec::new();
+        for opt in options {
+            if let Some(short) = opt.short {
+                match longs.iter().find(|(c, _)| *c == short) {
+                    Some((_, name)) => assert_eq!(*name, opt.name, "-{}", short),
+                    None => longs.push((short, opt.name)),
+                }
+            }
+        }
+
+        assert!(parse(&args("repair a.png -f")).is_err());
+    }
+}
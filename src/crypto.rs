@@ -0,0 +1,312 @@
+use std::io;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use crate::error::{Error, Result};
+
+// magic(4) + version(1) + kdf(1) + memory_cost(4) + time_cost(4) + parallelism(4) + salt(16) + nonce(24)
+const MAGIC: [u8; 4] = *b"PMEC";
+const VERSION: u8 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 4 + 1 + 1 + 12 + SALT_LEN + NONCE_LEN;
+// fixed, since the seed must be reproducible from the passphrase alone
+const SEED_SALT: [u8; SALT_LEN] = *b"pngme-scatter-v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    m_memory_cost: u32,
+    m_time_cost: u32,
+    m_parallelism: u32,
+}
+
+impl KdfParams {
+    const DEFAULT_MEMORY_COST: u32 = 19 * 1024;
+    const DEFAULT_TIME_COST: u32 = 2;
+    const DEFAULT_PARALLELISM: u32 = 1;
+    // the kdf runs before the tag is checked, so the params read from a file header are capped
+    // near the defaults to keep a crafted payload from costing much memory or time
+    pub const MAX_MEMORY_COST: u32 = 4 * Self::DEFAULT_MEMORY_COST;
+    pub const MAX_TIME_COST: u32 = 4 * Self::DEFAULT_TIME_COST;
+    pub const MAX_PARALLELISM: u32 = 4 * Self::DEFAULT_PARALLELISM;
+
+    // `memory_cost` is given in KiB
+    pub fn new(memory_cost: u32, time_cost: u32, parallelism: u32) -> Result<Self> {
+        let params = Self {
+            m_memory_cost: memory_cost,
+            m_time_cost: time_cost,
+            m_parallelism: parallelism,
+        };
+        params.argon2()?;
+
+        return Ok(params);
+    }
+
+    pub fn memory_cost(&self) -> u32 {
+        return self.m_memory_cost;
+    }
+
+    pub fn time_cost(&self) -> u32 {
+        return self.m_time_cost;
+    }
+
+    pub fn parallelism(&self) -> u32 {
+        return self.m_parallelism;
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>> {
+        if self.m_memory_cost > Self::MAX_MEMORY_COST
+            || self.m_time_cost > Self::MAX_TIME_COST
+            || self.m_parallelism > Self::MAX_PARALLELISM
+        {
+            return Err(Error::KdfParamsTooCostly {
+                memory_cost: self.m_memory_cost,
+                time_cost: self.m_time_cost,
+                parallelism: self.m_parallelism,
+            });
+        }
+
+        let params = Params::new(
+            self.m_memory_cost,
+            self.m_time_cost,
+            self.m_parallelism,
+            Some(KEY_LEN),
+        )
+        .map_err(|_| Error::InvalidKdfParams)?;
+
+        return Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params));
+    }
+
+    fn derive_key(&self, passphrase: &[u8], salt: &[u8]) -> Result<[u8; KEY_LEN]> {
+        let mut key = [0; KEY_LEN];
+        self.argon2()?
+            .hash_password_into(passphrase, salt, &mut key)
+            .map_err(|_| Error::InvalidKdfParams)?;
+
+        return Ok(key);
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        return Self {
+            m_memory_cost: Self::DEFAULT_MEMORY_COST,
+            m_time_cost: Self::DEFAULT_TIME_COST,
+            m_parallelism: Self::DEFAULT_PARALLELISM,
+        };
+    }
+}
+
+// a plain message may start with the magic too, so only a payload whose whole header parses
+// counts as encrypted
+pub fn is_encrypted(data: &[u8]) -> bool {
+    return parse_header(data).is_ok();
+}
+
+// the kdf params, salt and nonce of an encrypted payload
+fn parse_header(data: &[u8]) -> Result<(KdfParams, &[u8], &[u8])> {
+    if data.len() < 6 || data[0..4] != MAGIC {
+        return Err(Error::MalformedEncryptedPayload);
+    }
+    if data[4] != VERSION {
+        return Err(Error::UnsupportedEncryptionVersion(data[4]));
+    }
+    if data[5] != KDF_ARGON2ID || data.len() < HEADER_LEN + TAG_LEN {
+        return Err(Error::MalformedEncryptedPayload);
+    }
+
+    let params = KdfParams {
+        m_memory_cost: u32::from_be_bytes(data[6..10].try_into().unwrap()),
+        m_time_cost: u32::from_be_bytes(data[10..14].try_into().unwrap()),
+        m_parallelism: u32::from_be_bytes(data[14..18].try_into().unwrap()),
+    };
+    params.argon2()?;
+    let salt = &data[18..(18 + SALT_LEN)];
+    let nonce = &data[(18 + SALT_LEN)..HEADER_LEN];
+
+    return Ok((params, salt, nonce));
+}
+
+pub fn encrypt(plaintext: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    return encrypt_with_params(plaintext, passphrase, &KdfParams::default());
+}
+
+pub fn encrypt_with_params(
+    plaintext: &[u8],
+    passphrase: &[u8],
+    params: &KdfParams,
+) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    getrandom::getrandom(&mut salt).map_err(|err| Error::Io(io::Error::other(err.to_string())))?;
+    getrandom::getrandom(&mut nonce).map_err(|err| Error::Io(io::Error::other(err.to_string())))?;
+
+    let mut header: Vec<u8> = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.push(KDF_ARGON2ID);
+    header.extend_from_slice(&params.memory_cost().to_be_bytes());
+    header.extend_from_slice(&params.time_cost().to_be_bytes());
+    header.extend_from_slice(&params.parallelism().to_be_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    // the header is authenticated too, so tampering with the kdf params is detected
+    let key = params.derive_key(passphrase, &salt)?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    let payload = Payload {
+        msg: plaintext,
+        aad: &header,
+    };
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), payload)
+        .map_err(|_| Error::EncryptionFailed)?;
+
+    header.extend_from_slice(&ciphertext);
+    return Ok(header);
+}
+
+// PRNG seed for the keyed scattered-pixel mode
+pub fn derive_seed(passphrase: &[u8]) -> Result<[u8; KEY_LEN]> {
+    return derive_seed_with_params(passphrase, &KdfParams::default());
+}
+
+pub fn derive_seed_with_params(passphrase: &[u8], params: &KdfParams) -> Result<[u8; KEY_LEN]> {
+    return params.derive_key(passphrase, &SEED_SALT);
+}
+
+pub fn decrypt(data: &[u8], passphrase: &[u8]) -> Result<Vec<u8>> {
+    let (params, salt, nonce) = parse_header(data)?;
+    let key = params.derive_key(passphrase, salt)?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    let payload = Payload {
+        msg: &data[HEADER_LEN..],
+        aad: &data[..HEADER_LEN],
+    };
+
+    return cipher
+        .decrypt(XNonce::from_slice(nonce), payload)
+        .map_err(|_| Error::DecryptionFailed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_params() -> KdfParams {
+        return KdfParams::new(64, 1, 1).unwrap();
+    }
+
+    #[test]
+    pub fn test_crypto_round_trip() {
+        let msg = b"This is where your secret message will be!";
+        let data = encrypt_with_params(msg, b"hunter2", &test_params()).unwrap();
+
+        assert!(is_encrypted(&data));
+        assert_eq!(data.len(), HEADER_LEN + msg.len() + TAG_LEN);
+        assert!(!data.windows(msg.len()).any(|w| w == msg));
+        assert_eq!(decrypt(&data, b"hunter2").unwrap(), msg);
+    }
+
+    #[test]
+    pub fn test_crypto_wrong_passphrase() {
+        let data = encrypt_with_params(b"secret", b"hunter2", &test_params()).unwrap();
+        assert!(matches!(
+            decrypt(&data, b"hunter3"),
+            Err(Error::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    pub fn test_crypto_tampered_payload() {
+        let data = encrypt_with_params(b"secret", b"hunter2", &test_params()).unwrap();
+
+        let mut tampered = data.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(matches!(
+            decrypt(&tampered, b"hunter2"),
+            Err(Error::DecryptionFailed)
+        ));
+
+        // bumping the time cost changes the derived key and the authenticated header
+        let mut tampered = data.clone();
+        tampered[13] += 1;
+        assert!(matches!(
+            decrypt(&tampered, b"hunter2"),
+            Err(Error::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    pub fn test_crypto_malformed_header() {
+        let data = encrypt_with_params(b"secret", b"hunter2", &test_params()).unwrap();
+
+        assert!(!is_encrypted(b"plain text"));
+        // a plain message starting with the magic is still plain
+        assert!(!is_encrypted(b"PMEC hello"));
+        assert!(!is_encrypted(&data[..HEADER_LEN + TAG_LEN - 1]));
+        assert!(is_encrypted(&data[..HEADER_LEN + TAG_LEN]));
+        assert!(matches!(
+            decrypt(b"plain text", b"hunter2"),
+            Err(Error::MalformedEncryptedPayload)
+        ));
+        assert!(matches!(
+            decrypt(&data[..HEADER_LEN - 1], b"hunter2"),
+            Err(Error::MalformedEncryptedPayload)
+        ));
+
+        let mut bad_version = data.clone();
+        bad_version[4] = 9;
+        assert!(matches!(
+            decrypt(&bad_version, b"hunter2"),
+            Err(Error::UnsupportedEncryptionVersion(9))
+        ));
+
+        let mut bad_params = data.clone();
+        bad_params[6..10].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            decrypt(&bad_params, b"hunter2"),
+            Err(Error::KdfParamsTooCostly { .. })
+        ));
+
+        // just over the caps, which would still be accepted by argon2
+        for (range, value) in [
+            (6..10, KdfParams::MAX_MEMORY_COST + 1),
+            (10..14, KdfParams::MAX_TIME_COST + 1),
+            (14..18, KdfParams::MAX_PARALLELISM + 1),
+        ] {
+            let mut costly = data.clone();
+            costly[range].copy_from_slice(&value.to_be_bytes());
+            assert!(!is_encrypted(&costly));
+            assert!(matches!(
+                decrypt(&costly, b"hunter2"),
+                Err(Error::KdfParamsTooCostly { .. })
+            ));
+        }
+        assert!(KdfParams::new(KdfParams::MAX_MEMORY_COST, KdfParams::MAX_TIME_COST, 1).is_ok());
+        assert!(KdfParams::new(KdfParams::MAX_MEMORY_COST + 1, 1, 1).is_err());
+    }
+
+    #[test]
+    pub fn test_crypto_derive_seed() {
+        let seed = derive_seed_with_params(b"hunter2", &test_params()).unwrap();
+        assert_eq!(
+            derive_seed_with_params(b"hunter2", &test_params()).unwrap(),
+            seed
+        );
+        assert_ne!(
+            derive_seed_with_params(b"hunter3", &test_params()).unwrap(),
+            seed
+        );
+        assert_ne!(
+            derive_seed_with_params(b"hunter2", &KdfParams::new(128, 1, 1).unwrap()).unwrap(),
+            seed
+        );
+    }
+}
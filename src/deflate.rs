@@ -0,0 +1,664 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::inflate::{self, CODE_LENGTH_ORDER, DIST_BASE, DIST_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+const MAX_STORED_LEN: usize = 65535;
+// tokens per block; each block gets its own choice of stored, fixed or dynamic encoding
+const BLOCK_TOKENS: usize = 1 << 14;
+const END_OF_BLOCK: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionLevel {
+    Stored,
+    Fast,
+    Default,
+    Best,
+}
+
+impl CompressionLevel {
+    // (max hash chain length, length at which to stop searching, lazy matching)
+    fn params(&self) -> (usize, usize, bool) {
+        match self {
+            Self::Stored => return (0, 0, false),
+            Self::Fast => return (8, 32, false),
+            Self::Default => return (64, 128, true),
+            Self::Best => return (1024, MAX_MATCH, true),
+        }
+    }
+
+    // FLEVEL hint in the zlib header, with FCHECK chosen for 0x78 as CMF
+    fn zlib_flags(&self) -> u8 {
+        match self {
+            Self::Stored | Self::Fast => return 0x01,
+            Self::Default => return 0x9C,
+            Self::Best => return 0xDA,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Literal(u8),
+    Match { len: u16, dist: u16 },
+}
+
+struct BitWriter {
+    m_out: Vec<u8>,
+    m_bit_buf: u64,
+    m_bit_count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        return Self {
+            m_out: Vec::new(),
+            m_bit_buf: 0,
+            m_bit_count: 0,
+        };
+    }
+
+    fn write_bits(&mut self, value: u32, n: u32) {
+        self.m_bit_buf |= (value as u64) << self.m_bit_count;
+        self.m_bit_count += n;
+        while self.m_bit_count >= 8 {
+            self.m_out.push(self.m_bit_buf as u8);
+            self.m_bit_buf >>= 8;
+            self.m_bit_count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit
+    fn write_code(&mut self, code: u16, len: u8) {
+        let reversed = code.reverse_bits() >> (16 - len as u32);
+        self.write_bits(reversed as u32, len as u32);
+    }
+
+    fn align(&mut self) {
+        if self.m_bit_count > 0 {
+            self.write_bits(0, 8 - self.m_bit_count);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.align();
+        return self.m_out;
+    }
+}
+
+// a Huffman code as per-symbol lengths and canonical codes
+struct Code {
+    m_lengths: Vec<u8>,
+    m_codes: Vec<u16>,
+}
+
+impl Code {
+    fn from_lengths(lengths: Vec<u8>) -> Self {
+        let mut counts = [0u16; 16];
+        for len in lengths.iter() {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut next_code = [0u16; 16];
+        let mut code: u16 = 0;
+        for len in 1..16 {
+            code = (code + counts[len - 1]) << 1;
+            next_code[len] = code;
+        }
+
+        let mut m_codes = vec![0u16; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                m_codes[symbol] = next_code[*len as usize];
+                next_code[*len as usize] += 1;
+            }
+        }
+
+        return Self {
+            m_lengths: lengths,
+            m_codes,
+        };
+    }
+
+    // optimal code for `freqs` with no length above `limit`
+    fn from_freqs(freqs: &[u32], limit: u8) -> Self {
+        return Self::from_lengths(huffman_lengths(freqs, limit));
+    }
+
+    fn fixed_lit_len() -> Self {
+        let mut lengths = vec![0u8; 288];
+        lengths[0..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..288].fill(8);
+        return Self::from_lengths(lengths);
+    }
+
+    fn fixed_dist() -> Self {
+        return Self::from_lengths(vec![5; 30]);
+    }
+
+    fn write(&self, writer: &mut BitWriter, symbol: usize) {
+        writer.write_code(self.m_codes[symbol], self.m_lengths[symbol]);
+    }
+
+    fn cost(&self, freqs: &[u32]) -> usize {
+        return freqs
+            .iter()
+            .zip(self.m_lengths.iter())
+            .map(|(freq, len)| *freq as usize * *len as usize)
+            .sum();
+    }
+}
+
+// compresses `data` into a raw DEFLATE stream (RFC 1951)
+pub fn deflate(data: &[u8], level: CompressionLevel) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+
+    if level == CompressionLevel::Stored {
+        write_stored(&mut writer, data, true);
+        return writer.finish();
+    }
+
+    let tokens = find_tokens(data, level);
+    let mut start = 0;
+    let mut blocks = tokens.chunks(BLOCK_TOKENS).peekable();
+    if blocks.peek().is_none() {
+        write_block(&mut writer, &[], &[], true);
+    }
+    while let Some(block) = blocks.next() {
+        let span: usize = block.iter().map(token_span).sum();
+        let last = blocks.peek().is_none();
+        write_block(&mut writer, block, &data[start..(start + span)], last);
+        start += span;
+    }
+
+    return writer.finish();
+}
+
+// compresses `data` into a zlib stream (RFC 1950)
+pub fn zlib_compress(data: &[u8], level: CompressionLevel) -> Vec<u8> {
+    let mut out: Vec<u8> = vec![0x78, level.zlib_flags()];
+    out.extend(deflate(data, level));
+    out.extend_from_slice(&inflate::adler32(data).to_be_bytes());
+    return out;
+}
+
+fn token_span(token: &Token) -> usize {
+    match token {
+        Token::Literal(_) => return 1,
+        Token::Match { len, .. } => return *len as usize,
+    }
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let value = ((data[i] as u32) << 16) | ((data[i + 1] as u32) << 8) | data[i + 2] as u32;
+    return (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize;
+}
+
+// LZ77 parse of `data` using hash chains over the last 32K bytes
+fn find_tokens(data: &[u8], level: CompressionLevel) -> Vec<Token> {
+    let (max_chain, nice_len, lazy) = level.params();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+
+    let insert = |i: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+    let longest_match = |i: usize, head: &Vec<usize>, prev: &Vec<usize>| -> (usize, usize) {
+        if i + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+
+        let max_len = MAX_MATCH.min(data.len() - i);
+        let (mut best_len, mut best_dist) = (0, 0);
+        let mut candidate = head[hash(data, i)];
+        let mut chain = max_chain;
+        while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain > 0 {
+            let len = data[candidate..]
+                .iter()
+                .zip(data[i..(i + max_len)].iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            if len > best_len {
+                best_len = len;
+                best_dist = i - candidate;
+                if len >= nice_len {
+                    break;
+                }
+            }
+            candidate = prev[candidate];
+            chain -= 1;
+        }
+
+        if best_len < MIN_MATCH {
+            return (0, 0);
+        }
+        return (best_len, best_dist);
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut len, mut dist) = longest_match(i, &head, &prev);
+        insert(i, &mut head, &mut prev);
+
+        // lazy matching: emit a literal instead if the next position has a longer match
+        if lazy && len >= MIN_MATCH && len < nice_len && i + 1 < data.len() {
+            let (next_len, next_dist) = longest_match(i + 1, &head, &prev);
+            if next_len > len {
+                tokens.push(Token::Literal(data[i]));
+                i += 1;
+                len = next_len;
+                dist = next_dist;
+                insert(i, &mut head, &mut prev);
+            }
+        }
+
+        if len >= MIN_MATCH {
+            tokens.push(Token::Match {
+                len: len as u16,
+                dist: dist as u16,
+            });
+            for j in (i + 1)..(i + len) {
+                insert(j, &mut head, &mut prev);
+            }
+            i += len;
+        } else {
+            tokens.push(Token::Literal(data[i]));
+            i += 1;
+        }
+    }
+
+    return tokens;
+}
+
+// index of the base value covering `value`, for the length and distance symbol tables
+fn base_index(bases: &[u16], value: usize) -> usize {
+    return bases.partition_point(|base| *base as usize <= value) - 1;
+}
+
+fn length_symbol(len: usize) -> (usize, u32, u32) {
+    // 258 has its own symbol even though 227 + 5 extra bits could also cover it
+    let i = if len == MAX_MATCH {
+        LENGTH_BASE.len() - 1
+    } else {
+        base_index(&LENGTH_BASE[..(LENGTH_BASE.len() - 1)], len)
+    };
+    return (
+        257 + i,
+        (len - LENGTH_BASE[i] as usize) as u32,
+        LENGTH_EXTRA[i] as u32,
+    );
+}
+
+fn dist_symbol(dist: usize) -> (usize, u32, u32) {
+    let i = base_index(&DIST_BASE, dist);
+    return (
+        i,
+        (dist - DIST_BASE[i] as usize) as u32,
+        DIST_EXTRA[i] as u32,
+    );
+}
+
+fn write_stored(writer: &mut BitWriter, data: &[u8], last: bool) {
+    // an empty input still needs one (empty) block
+    let count = data.len().div_ceil(MAX_STORED_LEN).max(1);
+    for i in 0..count {
+        let piece = &data[(i * MAX_STORED_LEN)..data.len().min((i + 1) * MAX_STORED_LEN)];
+        writer.write_bits((last && i + 1 == count) as u32, 1);
+        writer.write_bits(0, 2);
+        writer.align();
+        writer.write_bits(piece.len() as u32, 16);
+        writer.write_bits(!(piece.len() as u32) & 0xFFFF, 16);
+        for byte in piece {
+            writer.write_bits(*byte as u32, 8);
+        }
+    }
+}
+
+fn write_block(writer: &mut BitWriter, tokens: &[Token], data: &[u8], last: bool) {
+    let mut lit_freqs = [0u32; 286];
+    let mut dist_freqs = [0u32; 30];
+    let mut extra_bits: usize = 0;
+    for token in tokens {
+        match token {
+            Token::Literal(byte) => lit_freqs[*byte as usize] += 1,
+            Token::Match { len, dist } => {
+                let (symbol, _, len_extra) = length_symbol(*len as usize);
+                let (dist_symbol, _, dist_extra) = dist_symbol(*dist as usize);
+                lit_freqs[symbol] += 1;
+                dist_freqs[dist_symbol] += 1;
+                extra_bits += (len_extra + dist_extra) as usize;
+            }
+        }
+    }
+    lit_freqs[END_OF_BLOCK] += 1;
+
+    let fixed_lit_len = Code::fixed_lit_len();
+    let fixed_dist = Code::fixed_dist();
+    let fixed_cost = 3 + fixed_lit_len.cost(&lit_freqs) + fixed_dist.cost(&dist_freqs) + extra_bits;
+
+    let lit_len = Code::from_freqs(&lit_freqs, 15);
+    let dist = Code::from_freqs(&dist_freqs, 15);
+    let header = DynamicHeader::new(&lit_len, &dist);
+    let dynamic_cost =
+        3 + header.cost() + lit_len.cost(&lit_freqs) + dist.cost(&dist_freqs) + extra_bits;
+
+    // 3 header bits, up to 7 alignment bits and LEN/NLEN for every 64K piece
+    let stored_cost = data.len().div_ceil(MAX_STORED_LEN).max(1) * (3 + 7 + 32) + data.len() * 8;
+
+    if stored_cost < fixed_cost.min(dynamic_cost) {
+        write_stored(writer, data, last);
+    } else if fixed_cost <= dynamic_cost {
+        writer.write_bits(last as u32, 1);
+        writer.write_bits(1, 2);
+        write_tokens(writer, tokens, &fixed_lit_len, &fixed_dist);
+    } else {
+        writer.write_bits(last as u32, 1);
+        writer.write_bits(2, 2);
+        header.write(writer);
+        write_tokens(writer, tokens, &lit_len, &dist);
+    }
+}
+
+fn write_tokens(writer: &mut BitWriter, tokens: &[Token], lit_len: &Code, dist: &Code) {
+    for token in tokens {
+        match token {
+            Token::Literal(byte) => lit_len.write(writer, *byte as usize),
+            Token::Match {
+                len,
+                dist: distance,
+            } => {
+                let (symbol, extra, extra_len) = length_symbol(*len as usize);
+                lit_len.write(writer, symbol);
+                writer.write_bits(extra, extra_len);
+
+                let (symbol, extra, extra_len) = dist_symbol(*distance as usize);
+                dist.write(writer, symbol);
+                writer.write_bits(extra, extra_len);
+            }
+        }
+    }
+    lit_len.write(writer, END_OF_BLOCK);
+}
+
+// run-length encoded code lengths of a dynamic block, with the code used to write them
+struct DynamicHeader {
+    m_hlit: usize,
+    m_hdist: usize,
+    m_hclen: usize,
+    m_runs: Vec<(usize, u32, u32)>,
+    m_code: Code,
+}
+
+impl DynamicHeader {
+    fn new(lit_len: &Code, dist: &Code) -> Self {
+        let used = |lengths: &[u8], min: usize| {
+            let count = lengths
+                .iter()
+                .rposition(|len| *len != 0)
+                .map_or(0, |i| i + 1);
+            return count.max(min);
+        };
+        let m_hlit = used(&lit_len.m_lengths, 257);
+        let m_hdist = used(&dist.m_lengths, 1);
+
+        let lengths: Vec<u8> = lit_len.m_lengths[..m_hlit]
+            .iter()
+            .chain(dist.m_lengths[..m_hdist].iter())
+            .copied()
+            .collect();
+        let m_runs = run_lengths(&lengths);
+
+        let mut freqs = [0u32; 19];
+        for (symbol, _, _) in m_runs.iter() {
+            freqs[*symbol] += 1;
+        }
+        let m_code = Code::from_freqs(&freqs, 7);
+        let m_hclen = CODE_LENGTH_ORDER
+            .iter()
+            .rposition(|symbol| m_code.m_lengths[*symbol] != 0)
+            .map_or(0, |i| i + 1)
+            .max(4);
+
+        return Self {
+            m_hlit,
+            m_hdist,
+            m_hclen,
+            m_runs,
+            m_code,
+        };
+    }
+
+    fn cost(&self) -> usize {
+        let runs: usize = self
+            .m_runs
+            .iter()
+            .map(|(symbol, _, extra_len)| {
+                self.m_code.m_lengths[*symbol] as usize + *extra_len as usize
+            })
+            .sum();
+        return 5 + 5 + 4 + 3 * self.m_hclen + runs;
+    }
+
+    fn write(&self, writer: &mut BitWriter) {
+        writer.write_bits((self.m_hlit - 257) as u32, 5);
+        writer.write_bits((self.m_hdist - 1) as u32, 5);
+        writer.write_bits((self.m_hclen - 4) as u32, 4);
+        for symbol in CODE_LENGTH_ORDER.iter().take(self.m_hclen) {
+            writer.write_bits(self.m_code.m_lengths[*symbol] as u32, 3);
+        }
+        for (symbol, extra, extra_len) in self.m_runs.iter() {
+            self.m_code.write(writer, *symbol);
+            writer.write_bits(*extra, *extra_len);
+        }
+    }
+}
+
+// encodes code lengths with the repeat symbols 16 (previous), 17 and 18 (zeros)
+fn run_lengths(lengths: &[u8]) -> Vec<(usize, u32, u32)> {
+    let mut runs: Vec<(usize, u32, u32)> = Vec::new();
+    let mut i = 0;
+    while i < lengths.len() {
+        let value = lengths[i];
+        let run = lengths[i..].iter().take_while(|len| **len == value).count();
+
+        if value == 0 && run >= 11 {
+            let n = run.min(138);
+            runs.push((18, (n - 11) as u32, 7));
+            i += n;
+        } else if value == 0 && run >= 3 {
+            runs.push((17, (run - 3) as u32, 3));
+            i += run;
+        } else if value != 0 && run >= 4 {
+            runs.push((value as usize, 0, 0));
+            let n = (run - 1).min(6);
+            runs.push((16, (n - 3) as u32, 2));
+            i += 1 + n;
+        } else {
+            runs.push((value as usize, 0, 0));
+            i += 1;
+        }
+    }
+
+    return runs;
+}
+
+// Huffman code lengths for `freqs`, flattening the frequencies until no length exceeds `limit`
+fn huffman_lengths(freqs: &[u32], limit: u8) -> Vec<u8> {
+    let mut freqs: Vec<u32> = freqs.to_vec();
+    let used: Vec<usize> = (0..freqs.len()).filter(|i| freqs[*i] > 0).collect();
+
+    let mut lengths = vec![0u8; freqs.len()];
+    match used.len() {
+        0 => {
+            // a single unused code keeps decoders happy when no symbol is needed
+            lengths[0] = 1;
+            lengths[1] = 1;
+            return lengths;
+        }
+        1 => {
+            // pair the only symbol with another one so the code is complete
+            lengths[used[0]] = 1;
+            lengths[if used[0] == 0 { 1 } else { 0 }] = 1;
+            return lengths;
+        }
+        _ => (),
+    }
+
+    loop {
+        // nodes 0..n are leaves, later nodes are internal; `parent` links every node to its parent
+        let mut parent: Vec<usize> = vec![usize::MAX; used.len()];
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = used
+            .iter()
+            .enumerate()
+            .map(|(node, symbol)| Reverse((freqs[*symbol] as u64, node)))
+            .collect();
+        while heap.len() > 1 {
+            let Reverse((weight_a, a)) = heap.pop().unwrap();
+            let Reverse((weight_b, b)) = heap.pop().unwrap();
+            let node = parent.len();
+            parent.push(usize::MAX);
+            parent[a] = node;
+            parent[b] = node;
+            heap.push(Reverse((weight_a + weight_b, node)));
+        }
+
+        let mut max_len = 0;
+        for (node, symbol) in used.iter().enumerate() {
+            let mut depth = 0;
+            let mut current = node;
+            while parent[current] != usize::MAX {
+                current = parent[current];
+                depth += 1;
+            }
+            lengths[*symbol] = depth;
+            max_len = max_len.max(depth);
+        }
+
+        if max_len <= limit {
+            return lengths;
+        }
+        for symbol in used.iter() {
+            freqs[*symbol] = (freqs[*symbol] >> 1).max(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inflate::{inflate, zlib_decompress, DEFAULT_OUTPUT_LIMIT};
+
+    const LEVELS: [CompressionLevel; 4] = [
+        CompressionLevel::Stored,
+        CompressionLevel::Fast,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+    ];
+
+    fn pseudo_random(len: usize, seed: u32) -> Vec<u8> {
+        let mut seed = seed;
+        return (0..len)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                return (seed >> 16) as u8;
+            })
+            .collect();
+    }
+
+    fn samples() -> Vec<Vec<u8>> {
+        return vec![
+            Vec::new(),
+            vec![42],
+            b"hello".to_vec(),
+            b"abcabcabcabcabcabcabc".to_vec(),
+            b"In the beginning God created the heaven and the earth. ".repeat(50),
+            vec![0; 100_000],
+            pseudo_random(70_000, 7),
+            // mostly small values, like filtered image rows
+            pseudo_random(50_000, 11).iter().map(|b| b % 5).collect(),
+        ];
+    }
+
+    #[test]
+    pub fn test_zlib_round_trip() {
+        for data in samples() {
+            for level in LEVELS {
+                let compressed = zlib_compress(&data, level);
+                let decompressed = zlib_decompress(&compressed, DEFAULT_OUTPUT_LIMIT).unwrap();
+                assert_eq!(decompressed, data, "level {:?}", level);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_deflate_consumes_whole_stream() {
+        let data = b"In the beginning God created the heaven and the earth. ".repeat(10);
+        let compressed = deflate(&data, CompressionLevel::Default);
+        let (decompressed, consumed) = inflate(&compressed, DEFAULT_OUTPUT_LIMIT).unwrap();
+        assert_eq!(decompressed, data);
+        assert_eq!(consumed, compressed.len());
+    }
+
+    #[test]
+    pub fn test_deflate_block_types() {
+        // stored: BFINAL=1, BTYPE=00, then LEN=5 and NLEN
+        let compressed = deflate(b"hello", CompressionLevel::Stored);
+        assert_eq!(compressed, [1, 5, 0, 250, 255, 104, 101, 108, 108, 111]);
+
+        // short inputs are cheapest with the fixed code
+        let compressed = deflate(b"hello", CompressionLevel::Default);
+        assert_eq!(compressed[0] & 0b111, 0b011);
+
+        // skewed statistics are cheapest with a dynamic code
+        let data: Vec<u8> = pseudo_random(50_000, 11).iter().map(|b| b % 5).collect();
+        let compressed = deflate(&data, CompressionLevel::Default);
+        assert_eq!(compressed[0] & 0b111, 0b101);
+
+        // incompressible data falls back to stored blocks
+        let data = pseudo_random(10_000, 3);
+        let compressed = deflate(&data, CompressionLevel::Default);
+        assert_eq!(compressed[0] & 0b110, 0b000);
+        assert!(compressed.len() <= data.len() + 5);
+    }
+
+    #[test]
+    pub fn test_deflate_levels() {
+        let data = b"In the beginning God created the heaven and the earth. ".repeat(50);
+        let stored = zlib_compress(&data, CompressionLevel::Stored).len();
+        let fast = zlib_compress(&data, CompressionLevel::Fast).len();
+        let best = zlib_compress(&data, CompressionLevel::Best).len();
+
+        assert!(stored > data.len());
+        assert!(fast < data.len() / 10);
+        assert!(best <= fast);
+    }
+
+    #[test]
+    pub fn test_huffman_lengths_limit() {
+        // Fibonacci frequencies produce the deepest possible trees
+        let mut freqs = vec![1u32, 1];
+        while freqs.len() < 30 {
+            let n = freqs.len();
+            freqs.push(freqs[n - 1] + freqs[n - 2]);
+        }
+
+        let lengths = huffman_lengths(&freqs, 15);
+        assert!(lengths.iter().all(|len| *len <= 15 && *len > 0));
+
+        // Kraft equality holds for a complete code
+        let kraft: f64 = lengths.iter().map(|len| 0.5f64.powi(*len as i32)).sum();
+        assert!((kraft - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::str::{from_utf8, FromStr};
+
+use crate::error::{Error, Result};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+// textual representation of binary payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Hex,
+    // standard alphabet, padded
+    Base64,
+}
+
+impl Encoding {
+    pub fn encode(&self, bytes: &[u8]) -> Result<String> {
+        match self {
+            Self::Utf8 => return Ok(String::from(from_utf8(bytes).map_err(Error::InvalidUtf8)?)),
+            Self::Hex => return Ok(encode_hex(bytes)),
+            Self::Base64 => return Ok(encode_base64(bytes)),
+        }
+    }
+
+    pub fn decode(&self, text: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Utf8 => return Ok(text.as_bytes().to_vec()),
+            Self::Hex => return decode_hex(text.trim()),
+            Self::Base64 => return decode_base64(text.trim()),
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "utf8" | "utf-8" => return Ok(Self::Utf8),
+            "hex" => return Ok(Self::Hex),
+            "base64" => return Ok(Self::Base64),
+            _ => return Err(Error::InvalidUsage(format!("Unknown encoding {:?}", s))),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Utf8 => "utf8",
+            Self::Hex => "hex",
+            Self::Base64 => "base64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        text.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        text.push(HEX_DIGITS[(byte & 0x0F) as usize] as char);
+    }
+
+    return text;
+}
+
+pub fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return Err(Error::InvalidEncodedText("odd number of hex digits"));
+    }
+
+    let digit = |c: u8| match (c as char).to_digit(16) {
+        Some(d) => return Ok(d as u8),
+        None => return Err(Error::InvalidEncodedText("invalid hex digit")),
+    };
+    return text
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| Ok((digit(pair[0])? << 4) | digit(pair[1])?))
+        .collect();
+}
+
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let b = [
+            group[0],
+            *group.get(1).unwrap_or(&0),
+            *group.get(2).unwrap_or(&0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            // a group of k bytes fills k + 1 characters, the rest is padding
+            if i <= group.len() {
+                text.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    return text;
+}
+
+pub fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return Err(Error::InvalidEncodedText(
+            "base64 length is not a multiple of 4",
+        ));
+    }
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len() / 4 * 3);
+    for (g, group) in text.chunks(4).enumerate() {
+        let last = g + 1 == text.len() / 4;
+        let padding = group.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return Err(Error::InvalidEncodedText("misplaced base64 padding"));
+        }
+
+        let mut n: u32 = 0;
+        for c in group[..(4 - padding)].iter() {
+            let value = match BASE64_ALPHABET.iter().position(|a| a == c) {
+                Some(value) => value as u32,
+                None => return Err(Error::InvalidEncodedText("invalid base64 character")),
+            };
+            n = (n << 6) | value;
+        }
+        n <<= 6 * padding as u32;
+        bytes.extend_from_slice(&n.to_be_bytes()[1..(4 - padding)]);
+    }
+
+    return Ok(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_encoding_hex() {
+        assert_eq!(encode_hex(&[0x00, 0x7F, 0xA5, 0xFF]), "007fa5ff");
+        assert_eq!(
+            decode_hex("007FA5ff").unwrap(),
+            vec![0x00, 0x7F, 0xA5, 0xFF]
+        );
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    pub fn test_encoding_base64() {
+        for (bytes, text) in [
+            (&b""[..], ""),
+            (&b"f"[..], "Zg=="),
+            (&b"fo"[..], "Zm8="),
+            (&b"foo"[..], "Zm9v"),
+            (&b"foobar"[..], "Zm9vYmFy"),
+            (&[0xFB, 0xFF][..], "+/8="),
+        ] {
+            assert_eq!(encode_base64(bytes), text);
+            assert_eq!(decode_base64(text).unwrap(), bytes);
+        }
+
+        assert!(decode_base64("Zm9").is_err());
+        assert!(decode_base64("Zg==Zm9v").is_err());
+        assert!(decode_base64("Z===").is_err());
+        assert!(decode_base64("Zm9*").is_err());
+    }
+
+    #[test]
+    pub fn test_encoding_names() {
+        for encoding in [Encoding::Utf8, Encoding::Hex, Encoding::Base64] {
+            assert_eq!(Encoding::from_str(&encoding.to_string()).unwrap(), encoding);
+            let text = encoding.encode(b"pngme").unwrap();
+            assert_eq!(encoding.decode(&text).unwrap(), b"pngme");
+        }
+        assert!(Encoding::Utf8.encode(&[0xFF]).is_err());
+        assert!(Encoding::from_str("rot13").is_err());
+    }
+}
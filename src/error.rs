@@ -0,0 +1,449 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::str::Utf8Error;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::crypto::KdfParams;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    InvalidSignature,
+    TruncatedChunk {
+        offset: usize,
+        needed: usize,
+        available: usize,
+    },
+    InvalidLength {
+        offset: usize,
+        length: u32,
+    },
+    LengthMismatch {
+        declared: u32,
+        actual: usize,
+    },
+    InvalidCrc {
+        expected: u32,
+        actual: u32,
+    },
+    InvalidChunkType(Vec<u8>),
+    ReservedBitSet(ChunkType),
+    ChunkNotFound(String),
+    InvalidIndex {
+        index: usize,
+        len: usize,
+    },
+    InvalidOccurrence {
+        chunk_type: String,
+        index: usize,
+        count: usize,
+    },
+    InvalidUtf8(Utf8Error),
+    UnexpectedChunkType {
+        expected: String,
+        found: String,
+    },
+    MalformedChunk {
+        chunk_type: String,
+        reason: String,
+    },
+    InvalidDimensions {
+        width: u32,
+        height: u32,
+    },
+    InvalidBitDepth {
+        color_type: u8,
+        bit_depth: u8,
+    },
+    InvalidColorType(u8),
+    InvalidCompressionMethod(u8),
+    InvalidFilterMethod(u8),
+    InvalidInterlaceMethod(u8),
+    CheckFailed {
+        bad_crcs: usize,
+        violations: usize,
+    },
+    InvalidFilterType(u8),
+    ImageDataLength {
+        expected: usize,
+        actual: usize,
+    },
+    ImageTooLarge {
+        width: u32,
+        height: u32,
+    },
+    UnsupportedLsbImage {
+        color_type: u8,
+        bit_depth: u8,
+    },
+    InvalidLsbOptions(String),
+    CapacityExceeded {
+        needed: usize,
+        available: usize,
+    },
+    LsbPayloadNotFound,
+    UnsupportedLsbVersion(u8),
+    InvalidAnimation(String),
+    InvalidSequenceNumber {
+        expected: u32,
+        found: u32,
+    },
+    InvalidFrameIndex {
+        index: usize,
+        len: usize,
+    },
+    InvalidKeyword(String),
+    TextNotFound(String),
+    InvalidText(String),
+    InvalidLanguageTag(String),
+    InvalidZlibHeader,
+    InvalidDeflateData(&'static str),
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    OutputLimitExceeded(usize),
+    InvalidKdfParams,
+    KdfParamsTooCostly {
+        memory_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+    },
+    EncryptionFailed,
+    DecryptionFailed,
+    MalformedEncryptedPayload,
+    UnsupportedEncryptionVersion(u8),
+    PassphraseRequired,
+    InvalidEncodedText(&'static str),
+    CriticalCrcsSkipped {
+        count: usize,
+    },
+    InvalidBackupSuffix(String),
+    BackupExists(String),
+    InvalidArguments,
+    InvalidUsage(String),
+    Io(io::Error),
+}
+
+// broad classes of errors, e.g. for choosing an exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    // bad arguments or options from the caller
+    Usage,
+    // the chunk, frame or message asked for does not exist
+    NotFound,
+    // the input is not a well-formed PNG or payload
+    Corrupt,
+    Io,
+    Other,
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidLsbOptions(_)
+            | Self::InvalidBackupSuffix(_)
+            | Self::BackupExists(_)
+            | Self::InvalidKeyword(_)
+            | Self::InvalidLanguageTag(_)
+            | Self::PassphraseRequired
+            | Self::InvalidEncodedText(_)
+            | Self::UnsupportedLsbImage { .. }
+            | Self::CapacityExceeded { .. }
+            | Self::InvalidKdfParams
+            | Self::InvalidArguments
+            | Self::InvalidUsage(_) => return ErrorKind::Usage,
+            Self::ChunkNotFound(_)
+            | Self::InvalidIndex { .. }
+            | Self::InvalidOccurrence { .. }
+            | Self::LsbPayloadNotFound
+            | Self::InvalidFrameIndex { .. }
+            | Self::TextNotFound(_) => return ErrorKind::NotFound,
+            Self::InvalidSignature
+            | Self::TruncatedChunk { .. }
+            | Self::InvalidLength { .. }
+            | Self::LengthMismatch { .. }
+            | Self::InvalidCrc { .. }
+            | Self::CriticalCrcsSkipped { .. }
+            | Self::InvalidChunkType(_)
+            | Self::ReservedBitSet(_)
+            | Self::InvalidUtf8(_)
+            | Self::UnexpectedChunkType { .. }
+            | Self::MalformedChunk { .. }
+            | Self::InvalidDimensions { .. }
+            | Self::InvalidBitDepth { .. }
+            | Self::InvalidColorType(_)
+            | Self::InvalidCompressionMethod(_)
+            | Self::InvalidFilterMethod(_)
+            | Self::InvalidInterlaceMethod(_)
+            | Self::CheckFailed { .. }
+            | Self::InvalidFilterType(_)
+            | Self::ImageDataLength { .. }
+            | Self::UnsupportedLsbVersion(_)
+            | Self::InvalidAnimation(_)
+            | Self::InvalidSequenceNumber { .. }
+            | Self::InvalidText(_)
+            | Self::InvalidZlibHeader
+            | Self::InvalidDeflateData(_)
+            | Self::ChecksumMismatch { .. }
+            | Self::KdfParamsTooCostly { .. }
+            | Self::MalformedEncryptedPayload
+            | Self::UnsupportedEncryptionVersion(_) => return ErrorKind::Corrupt,
+            Self::Io(_) => return ErrorKind::Io,
+            // limits hit by a valid but huge image, and a wrong passphrase that cannot be told
+            // apart from a tampered payload
+            Self::ImageTooLarge { .. }
+            | Self::OutputLimitExceeded(_)
+            | Self::EncryptionFailed
+            | Self::DecryptionFailed => return ErrorKind::Other,
+        }
+    }
+
+    // shifts offset-carrying errors from a chunk-relative to a file-relative position
+    pub(crate) fn at_offset(self, base: usize) -> Self {
+        match self {
+            Self::TruncatedChunk {
+                offset,
+                needed,
+                available,
+            } => {
+                return Self::TruncatedChunk {
+                    offset: base + offset,
+                    needed,
+                    available,
+                }
+            }
+            Self::InvalidLength { offset, length } => {
+                return Self::InvalidLength {
+                    offset: base + offset,
+                    length,
+                }
+            }
+            other => return other,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSignature => write!(f, "Invalid PNG signature."),
+            Self::TruncatedChunk {
+                offset,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Truncated chunk at offset {}: needed {} bytes, {} available.",
+                offset, needed, available
+            ),
+            Self::InvalidLength { offset, length } => write!(
+                f,
+                "Invalid chunk length at offset {}: {} exceeds the limit of {}.",
+                offset,
+                length,
+                Chunk::MAX_LENGTH
+            ),
+            Self::LengthMismatch { declared, actual } => write!(
+                f,
+                "Chunk length mismatch: declared {} bytes, found {}.",
+                declared, actual
+            ),
+            Self::InvalidCrc { expected, actual } => write!(
+                f,
+                "Invalid CRC value: expected {:#010x}, found {:#010x}.",
+                expected, actual
+            ),
+            Self::InvalidChunkType(bytes) => write!(f, "Invalid chunk type bytes: {:?}.", bytes),
+            Self::ReservedBitSet(chunk_type) => {
+                write!(
+                    f,
+                    "Reserved bit is set in chunk type {:?}.",
+                    chunk_type.str()
+                )
+            }
+            Self::ChunkNotFound(chunk_type) => write!(f, "Chunk {:?} is not found.", chunk_type),
+            Self::InvalidIndex { index, len } => write!(
+                f,
+                "Invalid chunk index {}: the file has {} chunks.",
+                index, len
+            ),
+            Self::InvalidOccurrence {
+                chunk_type,
+                index,
+                count,
+            } => write!(
+                f,
+                "Invalid occurrence {} of chunk {:?}: the file has {} of them.",
+                index, chunk_type, count
+            ),
+            Self::InvalidUtf8(err) => write!(f, "Chunk data is not valid UTF-8: {}.", err),
+            Self::UnexpectedChunkType { expected, found } => write!(
+                f,
+                "Unexpected chunk type: expected {:?}, found {:?}.",
+                expected, found
+            ),
+            Self::MalformedChunk { chunk_type, reason } => {
+                write!(f, "Malformed {:?} chunk: {}.", chunk_type, reason)
+            }
+            Self::InvalidDimensions { width, height } => {
+                write!(f, "Invalid image dimensions {}x{}.", width, height)
+            }
+            Self::InvalidBitDepth {
+                color_type,
+                bit_depth,
+            } => write!(
+                f,
+                "Invalid bit depth {} for color type {}.",
+                bit_depth, color_type
+            ),
+            Self::InvalidColorType(color_type) => write!(f, "Invalid color type {}.", color_type),
+            Self::InvalidCompressionMethod(method) => {
+                write!(f, "Invalid compression method {}.", method)
+            }
+            Self::InvalidFilterMethod(method) => write!(f, "Invalid filter method {}.", method),
+            Self::InvalidInterlaceMethod(method) => {
+                write!(f, "Invalid interlace method {}.", method)
+            }
+            Self::CheckFailed {
+                bad_crcs,
+                violations,
+            } => match (bad_crcs, violations) {
+                (0, _) => write!(f, "Found {} structural violation(s).", violations),
+                (_, 0) => write!(f, "Found {} chunk(s) with a bad CRC.", bad_crcs),
+                _ => write!(
+                    f,
+                    "Found {} chunk(s) with a bad CRC and {} structural violation(s).",
+                    bad_crcs, violations
+                ),
+            },
+            Self::InvalidFilterType(filter) => {
+                write!(f, "Invalid scanline filter type {}.", filter)
+            }
+            Self::ImageDataLength { expected, actual } => write!(
+                f,
+                "Image data length mismatch: expected {} bytes, found {}.",
+                expected, actual
+            ),
+            Self::ImageTooLarge { width, height } => {
+                write!(
+                    f,
+                    "Image of {}x{} pixels is too large to handle in memory.",
+                    width, height
+                )
+            }
+            Self::UnsupportedLsbImage {
+                color_type,
+                bit_depth,
+            } => write!(
+                f,
+                "Cannot hide data in the pixels of color type {} at bit depth {}: 8- or 16-bit grayscale or truecolor samples are required.",
+                color_type, bit_depth
+            ),
+            Self::InvalidLsbOptions(reason) => write!(f, "Invalid LSB options: {}.", reason),
+            Self::CapacityExceeded { needed, available } => write!(
+                f,
+                "The image can hold {} bytes, but {} are needed.",
+                available, needed
+            ),
+            Self::LsbPayloadNotFound => write!(f, "No LSB payload is found in the image."),
+            Self::UnsupportedLsbVersion(version) => {
+                write!(f, "Unsupported LSB payload version {}.", version)
+            }
+            Self::InvalidAnimation(reason) => write!(f, "Invalid animation: {}.", reason),
+            Self::InvalidSequenceNumber { expected, found } => write!(
+                f,
+                "Invalid APNG sequence number: expected {}, found {}.",
+                expected, found
+            ),
+            Self::InvalidFrameIndex { index, len } => write!(
+                f,
+                "Invalid frame index {}: the animation has {} frames.",
+                index, len
+            ),
+            Self::InvalidKeyword(keyword) => write!(f, "Invalid text keyword {:?}.", keyword),
+            Self::TextNotFound(keyword) => write!(f, "Text {:?} is not found.", keyword),
+            Self::InvalidText(reason) => write!(f, "Invalid text: {}.", reason),
+            Self::InvalidLanguageTag(tag) => write!(f, "Invalid language tag {:?}.", tag),
+            Self::InvalidZlibHeader => write!(f, "Invalid zlib header."),
+            Self::InvalidDeflateData(reason) => write!(f, "Invalid DEFLATE data: {}.", reason),
+            Self::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Adler-32 checksum mismatch: expected {:#010x}, found {:#010x}.",
+                expected, actual
+            ),
+            Self::OutputLimitExceeded(limit) => {
+                write!(f, "Decompressed data exceeds the limit of {} bytes.", limit)
+            }
+            Self::InvalidKdfParams => write!(f, "Invalid key derivation parameters."),
+            Self::KdfParamsTooCostly {
+                memory_cost,
+                time_cost,
+                parallelism,
+            } => write!(
+                f,
+                "Key derivation parameters too costly: {} KiB, {} passes and {} lanes, at most {} KiB, {} passes and {} lanes are allowed.",
+                memory_cost,
+                time_cost,
+                parallelism,
+                KdfParams::MAX_MEMORY_COST,
+                KdfParams::MAX_TIME_COST,
+                KdfParams::MAX_PARALLELISM
+            ),
+            Self::EncryptionFailed => write!(f, "Failed to encrypt the message."),
+            Self::DecryptionFailed => write!(
+                f,
+                "Failed to decrypt the message: wrong passphrase or corrupted data."
+            ),
+            Self::MalformedEncryptedPayload => write!(f, "Malformed encrypted payload."),
+            Self::UnsupportedEncryptionVersion(version) => {
+                write!(f, "Unsupported encrypted payload version {}.", version)
+            }
+            Self::PassphraseRequired => write!(
+                f,
+                "A passphrase is required: set the PNGME_PASSPHRASE environment variable."
+            ),
+            Self::InvalidEncodedText(reason) => write!(f, "Invalid encoded text: {}.", reason),
+            Self::CriticalCrcsSkipped { count } => write!(
+                f,
+                "Skipped {} critical chunk(s) with a bad CRC, whose data may be damaged: \
+                 they are only repaired when forced.",
+                count
+            ),
+            Self::InvalidBackupSuffix(suffix) => write!(
+                f,
+                "Invalid backup suffix {:?}: it must be non-empty and free of path separators.",
+                suffix
+            ),
+            Self::BackupExists(fname) => write!(
+                f,
+                "Backup file {} already exists: move it away or pick another --backup suffix.",
+                fname
+            ),
+            Self::InvalidArguments => write!(f, "Invalid parameters or parameter number."),
+            Self::InvalidUsage(reason) => write!(f, "{}.", reason),
+            Self::Io(err) => write!(f, "I/O error: {}.", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::InvalidUtf8(err) => return Some(err),
+            Self::Io(err) => return Some(err),
+            _ => return None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        return Self::Io(err);
+    }
+}
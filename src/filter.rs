@@ -0,0 +1,155 @@
+use crate::error::{Error, Result};
+
+pub(crate) fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let p = left as i16 + above as i16 - upper_left as i16;
+    let pa = (p - left as i16).abs();
+    let pb = (p - above as i16).abs();
+    let pc = (p - upper_left as i16).abs();
+    if pa <= pb && pa <= pc {
+        return left;
+    }
+    if pb <= pc {
+        return above;
+    }
+    return upper_left;
+}
+
+// reverses `filter` on `row` in place; `prev` is the unfiltered previous row (zeros for the first one)
+// and `bpp` the number of bytes per complete pixel, rounded up to 1
+pub(crate) fn unfilter(filter: u8, row: &mut [u8], prev: &[u8], bpp: usize) -> Result<()> {
+    match filter {
+        0 => (),
+        1 => {
+            for i in bpp..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
+        2 => {
+            for (byte, above) in row.iter_mut().zip(prev.iter()) {
+                *byte = byte.wrapping_add(*above);
+            }
+        }
+        3 => {
+            for i in 0..row.len() {
+                let left = if i >= bpp { row[i - bpp] } else { 0 };
+                row[i] = row[i].wrapping_add(((left as u16 + prev[i] as u16) / 2) as u8);
+            }
+        }
+        4 => {
+            for i in 0..row.len() {
+                let (left, upper_left) = if i >= bpp {
+                    (row[i - bpp], prev[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                row[i] = row[i].wrapping_add(paeth(left, prev[i], upper_left));
+            }
+        }
+        _ => return Err(Error::InvalidFilterType(filter)),
+    }
+
+    return Ok(());
+}
+
+// inverse of `unfilter`, writing the filtered bytes of `row` to `out`
+pub(crate) fn filter(filter: u8, row: &[u8], prev: &[u8], bpp: usize, out: &mut [u8]) {
+    for i in 0..row.len() {
+        let (left, upper_left) = if i >= bpp {
+            (row[i - bpp], prev[i - bpp])
+        } else {
+            (0, 0)
+        };
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => prev[i],
+            3 => ((left as u16 + prev[i] as u16) / 2) as u8,
+            4 => paeth(left, prev[i], upper_left),
+            _ => unreachable!("invalid filter type {}", filter),
+        };
+        out[i] = row[i].wrapping_sub(predictor);
+    }
+}
+
+// tries every filter type and keeps the one with the smallest sum of absolute (signed) bytes,
+// the heuristic recommended by the PNG spec
+pub(crate) fn filter_adaptive(row: &[u8], prev: &[u8], bpp: usize) -> (u8, Vec<u8>) {
+    let mut best: (u8, Vec<u8>) = (0, row.to_vec());
+    let mut best_score = usize::MAX;
+    let mut candidate = vec![0u8; row.len()];
+    for filter_type in 0..5 {
+        filter(filter_type, row, prev, bpp, &mut candidate);
+        let score: usize = candidate
+            .iter()
+            .map(|byte| (*byte as i8).unsigned_abs() as usize)
+            .sum();
+        if score < best_score {
+            best_score = score;
+            best = (filter_type, candidate.clone());
+        }
+    }
+
+    return best;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+        assert_eq!(paeth(0, 0, 0), 0);
+        assert_eq!(paeth(255, 0, 255), 0);
+    }
+
+    #[test]
+    pub fn test_unfilter() {
+        let prev = [10, 20, 30, 40];
+        for (filter, expected) in [
+            (0, [1, 2, 3, 4]),
+            (1, [1, 2, 4, 6]),
+            (2, [11, 22, 33, 44]),
+            (3, [6, 12, 21, 30]),
+            (4, [11, 22, 33, 44]),
+        ] {
+            let mut row = [1, 2, 3, 4];
+            unfilter(filter, &mut row, &prev, 2).unwrap();
+            assert_eq!(row, expected, "filter {}", filter);
+        }
+
+        let mut row = [1, 2, 3, 4];
+        assert!(matches!(
+            unfilter(5, &mut row, &prev, 2),
+            Err(Error::InvalidFilterType(5))
+        ));
+    }
+
+    #[test]
+    pub fn test_filter_round_trip() {
+        let prev: Vec<u8> = (0..24).map(|i| (i * 31 % 256) as u8).collect();
+        let row: Vec<u8> = (0..24).map(|i| (i * 97 % 256) as u8).collect();
+        for filter_type in 0..5 {
+            let mut filtered = vec![0u8; row.len()];
+            filter(filter_type, &row, &prev, 3, &mut filtered);
+            unfilter(filter_type, &mut filtered, &prev, 3).unwrap();
+            assert_eq!(filtered, row, "filter {}", filter_type);
+        }
+    }
+
+    #[test]
+    pub fn test_filter_adaptive() {
+        // a smooth gradient is best predicted from the left neighbour
+        let row: Vec<u8> = (0..16).map(|i| i * 10).collect();
+        let (filter_type, filtered) = filter_adaptive(&row, &[0; 16], 1);
+        assert_eq!(filter_type, 1);
+        assert_eq!(&filtered[1..], [10; 15]);
+
+        // and a repeated row from the one above
+        let (filter_type, filtered) = filter_adaptive(&row, &row, 1);
+        assert_eq!(filter_type, 2);
+        assert_eq!(filtered, [0; 16]);
+    }
+}
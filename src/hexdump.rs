@@ -0,0 +1,74 @@
+use std::fmt::Write;
+
+pub const BYTES_PER_LINE: usize = 16;
+
+// classic offset/hex/ASCII view of `data`, starting at `start` and showing at most `limit` bytes
+pub fn hexdump(data: &[u8], start: usize, limit: Option<usize>) -> String {
+    let start = start.min(data.len());
+    let end = match limit {
+        Some(limit) => start.saturating_add(limit).min(data.len()),
+        None => data.len(),
+    };
+
+    let mut out = String::new();
+    for line_start in (start..end).step_by(BYTES_PER_LINE) {
+        let line = &data[line_start..(line_start + BYTES_PER_LINE).min(end)];
+        let _ = write!(out, "{:08x} ", line_start);
+        for i in 0..BYTES_PER_LINE {
+            // an extra space between the two halves of the line
+            if i % 8 == 0 {
+                out.push(' ');
+            }
+            match line.get(i) {
+                Some(byte) => {
+                    let _ = write!(out, "{:02x} ", byte);
+                }
+                None => out.push_str("   "),
+            }
+        }
+
+        out.push_str(" |");
+        for byte in line {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                out.push(*byte as char);
+            } else {
+                out.push('.');
+            }
+        }
+        out.push_str("|\n");
+    }
+
+    if end < data.len() {
+        let _ = writeln!(out, "... {} more bytes", data.len() - end);
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_hexdump() {
+        let data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR pngme";
+        assert_eq!(
+            hexdump(data, 0, None),
+            "00000000  89 50 4e 47 0d 0a 1a 0a  00 00 00 0d 49 48 44 52  |.PNG........IHDR|\n\
+             00000010  20 70 6e 67 6d 65                                 | pngme|\n"
+        );
+        assert_eq!(hexdump(b"", 0, None), "");
+    }
+
+    #[test]
+    pub fn test_hexdump_truncated() {
+        let data: Vec<u8> = (0..40).collect();
+        let dump = hexdump(&data, 4, Some(20));
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("00000004  04 05 06 07"));
+        assert!(lines[1].starts_with("00000014  14 15 16 17  "));
+        assert_eq!(lines[2], "... 16 more bytes");
+
+        assert_eq!(hexdump(&data, 100, Some(8)), "");
+    }
+}
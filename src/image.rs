@@ -0,0 +1,844 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::deflate::{self, CompressionLevel};
+use crate::error::{Error, Result};
+use crate::filter;
+use crate::image_header::{ColorType, ImageHeader};
+use crate::png::Png;
+
+// (x offset, y offset, x step, y step) of the seven Adam7 passes
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+const NO_INTERLACE: [(usize, usize, usize, usize); 1] = [(0, 0, 1, 1)];
+// the compressed stream is split into IDAT chunks of at most this size
+const IDAT_CHUNK_LEN: usize = 1 << 15;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    Gray(u16),
+    Rgb(u16, u16, u16),
+    // alpha of the first palette entries, the remaining ones are opaque
+    Palette(Vec<u8>),
+}
+
+impl Transparency {
+    pub const CHUNK_TYPE: &'static str = "tRNS";
+
+    pub fn from_chunk(chunk: &Chunk, color_type: ColorType, palette_len: usize) -> Result<Self> {
+        let data = chunk.data();
+        let sample = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+        match color_type {
+            ColorType::Grayscale if data.len() == 2 => return Ok(Self::Gray(sample(0))),
+            ColorType::Rgb if data.len() == 6 => {
+                return Ok(Self::Rgb(sample(0), sample(1), sample(2)))
+            }
+            ColorType::Indexed if data.len() <= palette_len => {
+                return Ok(Self::Palette(data.to_vec()))
+            }
+            ColorType::GrayscaleAlpha | ColorType::Rgba => {
+                return Err(malformed(
+                    Self::CHUNK_TYPE,
+                    format!("not allowed for color type {}", color_type),
+                ))
+            }
+            _ => {
+                return Err(malformed(
+                    Self::CHUNK_TYPE,
+                    format!(
+                        "unexpected length {} for color type {}",
+                        data.len(),
+                        color_type
+                    ),
+                ))
+            }
+        }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let data = match self {
+            Self::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Self::Rgb(r, g, b) => [r, g, b].iter().flat_map(|v| v.to_be_bytes()).collect(),
+            Self::Palette(alphas) => alphas.clone(),
+        };
+
+        return Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE)?, data);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    m_width: u32,
+    m_height: u32,
+    m_color_type: ColorType,
+    m_bit_depth: u8,
+    m_palette: Option<Vec<[u8; 3]>>,
+    m_transparency: Option<Transparency>,
+    // unfiltered, non-interlaced scanlines, each padded to a whole byte
+    m_pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: u8,
+        pixels: Vec<u8>,
+    ) -> Result<Self> {
+        ImageHeader::new(width, height, bit_depth, color_type, false)?;
+        let expected = image_size(width, height, color_type.channels() * bit_depth as usize)?;
+        if pixels.len() != expected {
+            return Err(Error::ImageDataLength {
+                expected,
+                actual: pixels.len(),
+            });
+        }
+
+        return Ok(Self {
+            m_width: width,
+            m_height: height,
+            m_color_type: color_type,
+            m_bit_depth: bit_depth,
+            m_palette: None,
+            m_transparency: None,
+            m_pixels: pixels,
+        });
+    }
+
+    // decodes the IDAT stream of `png`, failing if it would inflate beyond `limit` bytes
+    pub fn from_png(png: &Png, limit: usize) -> Result<Self> {
+        let header = png.header_info()?;
+        let bits_per_pixel = header.bits_per_pixel();
+        let size = image_size(header.width(), header.height(), bits_per_pixel)?;
+
+        let passes: &[(usize, usize, usize, usize)] = if header.is_interlaced() {
+            &ADAM7_PASSES
+        } else {
+            &NO_INTERLACE
+        };
+        // every pass row is preceded by its filter type byte
+        let mut expected: usize = 0;
+        for pass in passes {
+            let (width, height) = pass_size(&header, pass);
+            if width > 0 && height > 0 {
+                expected += height * (1 + row_bytes(width, bits_per_pixel));
+            }
+        }
+        if expected > limit {
+            return Err(Error::OutputLimitExceeded(limit));
+        }
+
+        let mut image = Self::new(
+            header.width(),
+            header.height(),
+            header.color_type(),
+            header.bit_depth(),
+            vec![0; size],
+        )?;
+        image.m_palette = read_palette(png, header.color_type())?;
+        if let Some(chunk) = png.search_chunk(Transparency::CHUNK_TYPE) {
+            let palette_len = image.m_palette.as_ref().map_or(0, |palette| palette.len());
+            image.m_transparency = Some(Transparency::from_chunk(
+                chunk,
+                header.color_type(),
+                palette_len,
+            )?);
+        }
+
+        let data = png.image_data(expected)?;
+        if data.len() != expected {
+            return Err(Error::ImageDataLength {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        let row_len = image.row_bytes();
+        let filter_bpp = (bits_per_pixel / 8).max(1);
+        let mut offset = 0;
+        for pass in passes {
+            let (width, height) = pass_size(&header, pass);
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            let (x0, y0, dx, dy) = *pass;
+            let pass_row_len = row_bytes(width, bits_per_pixel);
+            let mut prev = vec![0u8; pass_row_len];
+            let mut row = vec![0u8; pass_row_len];
+            for r in 0..height {
+                row.copy_from_slice(&data[(offset + 1)..(offset + 1 + pass_row_len)]);
+                filter::unfilter(data[offset], &mut row, &prev, filter_bpp)?;
+                offset += 1 + pass_row_len;
+
+                let y = y0 + r * dy;
+                let dst = &mut image.m_pixels[(y * row_len)..((y + 1) * row_len)];
+                if dx == 1 {
+                    dst.copy_from_slice(&row);
+                } else {
+                    for i in 0..width {
+                        copy_pixel(&row, i, dst, x0 + i * dx, bits_per_pixel);
+                    }
+                }
+                std::mem::swap(&mut prev, &mut row);
+            }
+        }
+
+        return Ok(image);
+    }
+
+    pub fn set_palette(&mut self, palette: Vec<[u8; 3]>) -> Result<()> {
+        if matches!(
+            self.m_color_type,
+            ColorType::Grayscale | ColorType::GrayscaleAlpha
+        ) {
+            return Err(malformed(
+                "PLTE",
+                format!("not allowed for color type {}", self.m_color_type),
+            ));
+        }
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(malformed(
+                "PLTE",
+                format!("expected 1 to 256 entries, found {}", palette.len()),
+            ));
+        }
+
+        self.m_palette = Some(palette);
+        return Ok(());
+    }
+
+    pub fn set_transparency(&mut self, transparency: Transparency) -> Result<()> {
+        let palette_len = self.palette().map_or(0, |palette| palette.len());
+        Transparency::from_chunk(&transparency.to_chunk()?, self.m_color_type, palette_len)?;
+
+        let max = ((1u32 << self.m_bit_depth) - 1) as u16;
+        let in_range = match transparency {
+            Transparency::Gray(gray) => gray <= max,
+            Transparency::Rgb(r, g, b) => r <= max && g <= max && b <= max,
+            Transparency::Palette(_) => true,
+        };
+        if !in_range {
+            return Err(malformed(
+                Transparency::CHUNK_TYPE,
+                format!("sample out of range for bit depth {}", self.m_bit_depth),
+            ));
+        }
+
+        self.m_transparency = Some(transparency);
+        return Ok(());
+    }
+
+    // encodes the image as a non-interlaced PNG
+    pub fn to_png(&self, level: CompressionLevel) -> Result<Png> {
+        let header = ImageHeader::new(
+            self.m_width,
+            self.m_height,
+            self.m_bit_depth,
+            self.m_color_type,
+            false,
+        )?;
+        let mut chunks: Vec<Chunk> = vec![header.to_chunk()?];
+        match self.palette() {
+            Some(palette) => {
+                chunks.push(Chunk::new(ChunkType::from_str("PLTE")?, palette.concat())?)
+            }
+            None if self.m_color_type == ColorType::Indexed => {
+                return Err(Error::ChunkNotFound(String::from("PLTE")))
+            }
+            None => (),
+        }
+        if let Some(transparency) = self.transparency() {
+            chunks.push(transparency.to_chunk()?);
+        }
+
+        // filtering rarely pays off for palette and sub-byte images, so those rows stay unfiltered
+        let adaptive = self.m_color_type != ColorType::Indexed && self.m_bit_depth >= 8;
+        let row_len = self.row_bytes();
+        let filter_bpp = (self.bits_per_pixel() / 8).max(1);
+        let zeros = vec![0u8; row_len];
+        let mut prev: &[u8] = &zeros;
+        let mut raw: Vec<u8> = Vec::with_capacity((row_len + 1) * self.m_height as usize);
+        for row in self.m_pixels.chunks(row_len) {
+            if adaptive {
+                let (filter_type, filtered) = filter::filter_adaptive(row, prev, filter_bpp);
+                raw.push(filter_type);
+                raw.extend(filtered);
+            } else {
+                raw.push(0);
+                raw.extend_from_slice(row);
+            }
+            prev = row;
+        }
+
+        for data in deflate::zlib_compress(&raw, level).chunks(IDAT_CHUNK_LEN) {
+            chunks.push(Chunk::new(ChunkType::from_str("IDAT")?, data.to_vec())?);
+        }
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, Vec::new())?);
+
+        return Ok(Png::from_chunks(chunks));
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.m_width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.m_height;
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        return self.m_color_type;
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        return self.m_bit_depth;
+    }
+
+    pub fn palette(&self) -> Option<&[[u8; 3]]> {
+        return self.m_palette.as_deref();
+    }
+
+    pub fn transparency(&self) -> Option<&Transparency> {
+        return self.m_transparency.as_ref();
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        return &self.m_pixels;
+    }
+
+    pub(crate) fn pixels_mut(&mut self) -> &mut [u8] {
+        return &mut self.m_pixels;
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        return self.m_color_type.channels() * self.m_bit_depth as usize;
+    }
+
+    pub fn row_bytes(&self) -> usize {
+        return row_bytes(self.m_width as usize, self.bits_per_pixel());
+    }
+
+    // value of one channel of the pixel at (x, y); panics outside the image
+    pub fn sample(&self, x: u32, y: u32, channel: usize) -> u16 {
+        let row_len = self.row_bytes();
+        let row = &self.m_pixels[(y as usize * row_len)..((y as usize + 1) * row_len)];
+        let index = x as usize * self.m_color_type.channels() + channel;
+        match self.m_bit_depth {
+            16 => return u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+            8 => return row[index] as u16,
+            depth => return read_bits(row, index * depth as usize, depth as usize) as u16,
+        }
+    }
+
+    // 8-bit RGB copy of the image, or RGBA if it has any transparency
+    pub fn to_truecolor(&self) -> Result<Image> {
+        let has_alpha = matches!(
+            self.m_color_type,
+            ColorType::GrayscaleAlpha | ColorType::Rgba
+        ) || self.m_transparency.is_some();
+        let rgba = self.to_rgba8();
+        if has_alpha {
+            return Image::new(self.m_width, self.m_height, ColorType::Rgba, 8, rgba);
+        }
+
+        let rgb: Vec<u8> = rgba
+            .chunks(4)
+            .flat_map(|pixel| pixel[..3].iter().copied())
+            .collect();
+        return Image::new(self.m_width, self.m_height, ColorType::Rgb, 8, rgb);
+    }
+
+    // 8-bit RGBA pixels with the palette and tRNS applied
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let max = (1u32 << self.m_bit_depth) - 1;
+        let scale = |value: u16| -> u8 {
+            if self.m_bit_depth == 16 {
+                return (value >> 8) as u8;
+            }
+            return (value as u32 * 255 / max) as u8;
+        };
+
+        let mut out: Vec<u8> =
+            Vec::with_capacity(self.m_width as usize * self.m_height as usize * 4);
+        for y in 0..self.m_height {
+            for x in 0..self.m_width {
+                let sample = |channel: usize| self.sample(x, y, channel);
+                let rgba = match self.m_color_type {
+                    ColorType::Grayscale => {
+                        let gray = sample(0);
+                        let alpha = match self.m_transparency {
+                            Some(Transparency::Gray(key)) if key == gray => 0,
+                            _ => 255,
+                        };
+                        [scale(gray), scale(gray), scale(gray), alpha]
+                    }
+                    ColorType::Rgb => {
+                        let (r, g, b) = (sample(0), sample(1), sample(2));
+                        let alpha = match self.m_transparency {
+                            Some(Transparency::Rgb(kr, kg, kb)) if (kr, kg, kb) == (r, g, b) => 0,
+                            _ => 255,
+                        };
+                        [scale(r), scale(g), scale(b), alpha]
+                    }
+                    ColorType::Indexed => {
+                        let index = sample(0) as usize;
+                        let [r, g, b] = self
+                            .palette()
+                            .and_then(|palette| palette.get(index).copied())
+                            .unwrap_or([0, 0, 0]);
+                        let alpha = match &self.m_transparency {
+                            Some(Transparency::Palette(alphas)) => {
+                                alphas.get(index).copied().unwrap_or(255)
+                            }
+                            _ => 255,
+                        };
+                        [r, g, b, alpha]
+                    }
+                    ColorType::GrayscaleAlpha => {
+                        let gray = scale(sample(0));
+                        [gray, gray, gray, scale(sample(1))]
+                    }
+                    ColorType::Rgba => [
+                        scale(sample(0)),
+                        scale(sample(1)),
+                        scale(sample(2)),
+                        scale(sample(3)),
+                    ],
+                };
+                out.extend_from_slice(&rgba);
+            }
+        }
+
+        return out;
+    }
+}
+
+impl fmt::Display for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Image: {{ Width: {}, Height: {}, ColorType: {}, BitDepth: {}, Pixels: {} bytes}}",
+            self.width(),
+            self.height(),
+            self.color_type(),
+            self.bit_depth(),
+            self.pixels().len()
+        )
+    }
+}
+
+fn malformed(chunk_type: &str, reason: String) -> Error {
+    return Error::MalformedChunk {
+        chunk_type: String::from(chunk_type),
+        reason,
+    };
+}
+
+fn read_palette(png: &Png, color_type: ColorType) -> Result<Option<Vec<[u8; 3]>>> {
+    let chunk = match png.search_chunk("PLTE") {
+        Some(chunk) => chunk,
+        None if color_type == ColorType::Indexed => {
+            return Err(Error::ChunkNotFound(String::from("PLTE")))
+        }
+        None => return Ok(None),
+    };
+    // grayscale images have no use for a palette
+    if matches!(color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha) {
+        return Ok(None);
+    }
+
+    let data = chunk.data();
+    if data.is_empty() || data.len() % 3 != 0 || data.len() > 3 * 256 {
+        return Err(malformed("PLTE", format!("invalid length {}", data.len())));
+    }
+
+    return Ok(Some(
+        data.chunks(3)
+            .map(|entry| [entry[0], entry[1], entry[2]])
+            .collect(),
+    ));
+}
+
+fn row_bytes(width: usize, bits_per_pixel: usize) -> usize {
+    return (width * bits_per_pixel).div_ceil(8);
+}
+
+// size of the unfiltered image, guarding against headers too large for memory
+fn image_size(width: u32, height: u32, bits_per_pixel: usize) -> Result<usize> {
+    return (width as usize)
+        .checked_mul(bits_per_pixel)
+        .and_then(|bits| bits.div_ceil(8).checked_mul(height as usize))
+        .ok_or(Error::ImageTooLarge { width, height });
+}
+
+fn pass_size(header: &ImageHeader, pass: &(usize, usize, usize, usize)) -> (usize, usize) {
+    let (x0, y0, dx, dy) = *pass;
+    let width = (header.width() as usize).saturating_sub(x0).div_ceil(dx);
+    let height = (header.height() as usize).saturating_sub(y0).div_ceil(dy);
+    return (width, height);
+}
+
+// reads the `n`-bit value starting at bit `bit` of `row`, with `n` dividing 8
+pub(crate) fn read_bits(row: &[u8], bit: usize, n: usize) -> u8 {
+    let shift = 8 - n - bit % 8;
+    return (row[bit / 8] >> shift) & ((1u16 << n) - 1) as u8;
+}
+
+pub(crate) fn write_bits(row: &mut [u8], bit: usize, n: usize, value: u8) {
+    let shift = 8 - n - bit % 8;
+    let mask = (((1u16 << n) - 1) as u8) << shift;
+    row[bit / 8] = (row[bit / 8] & !mask) | ((value << shift) & mask);
+}
+
+fn copy_pixel(
+    src: &[u8],
+    src_index: usize,
+    dst: &mut [u8],
+    dst_index: usize,
+    bits_per_pixel: usize,
+) {
+    if bits_per_pixel >= 8 {
+        let n = bits_per_pixel / 8;
+        dst[(dst_index * n)..((dst_index + 1) * n)]
+            .copy_from_slice(&src[(src_index * n)..((src_index + 1) * n)]);
+        return;
+    }
+
+    let value = read_bits(src, src_index * bits_per_pixel, bits_per_pixel);
+    write_bits(dst, dst_index * bits_per_pixel, bits_per_pixel, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::deflate::{zlib_compress, CompressionLevel};
+    use crate::inflate::DEFAULT_OUTPUT_LIMIT;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        return Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).unwrap();
+    }
+
+    // PNG with the given header, ancillary chunks and raw (filtered) image data
+    fn build_png(header: &ImageHeader, chunks: &[Chunk], raw: &[u8]) -> Png {
+        let mut all = vec![header.to_chunk().unwrap()];
+        all.extend_from_slice(chunks);
+        all.push(chunk("IDAT", &zlib_compress(raw, CompressionLevel::Fast)));
+        all.push(chunk("IEND", &[]));
+        return Png::from_chunks(all);
+    }
+
+    fn decode(header: &ImageHeader, chunks: &[Chunk], raw: &[u8]) -> Result<Image> {
+        return Image::from_png(&build_png(header, chunks, raw), DEFAULT_OUTPUT_LIMIT);
+    }
+
+    // Adam7 version of `image`'s scanlines, every row with filter type 0
+    fn interlace(image: &Image, header: &ImageHeader) -> Vec<u8> {
+        let bits_per_pixel = image.bits_per_pixel();
+        let row_len = image.row_bytes();
+        let mut raw: Vec<u8> = Vec::new();
+        for pass in ADAM7_PASSES.iter() {
+            let (width, height) = pass_size(header, pass);
+            if width == 0 || height == 0 {
+                continue;
+            }
+            let (x0, y0, dx, dy) = *pass;
+            for r in 0..height {
+                let y = y0 + r * dy;
+                let src = &image.pixels()[(y * row_len)..((y + 1) * row_len)];
+                let mut row = vec![0u8; row_bytes(width, bits_per_pixel)];
+                for i in 0..width {
+                    copy_pixel(src, x0 + i * dx, &mut row, i, bits_per_pixel);
+                }
+                raw.push(0);
+                raw.extend(row);
+            }
+        }
+        return raw;
+    }
+
+    fn samples(image: &Image) -> Vec<u16> {
+        let channels = image.color_type().channels();
+        let mut samples: Vec<u16> = Vec::new();
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                samples.extend((0..channels).map(|channel| image.sample(x, y, channel)));
+            }
+        }
+        return samples;
+    }
+
+    #[test]
+    pub fn test_image_filters() {
+        let header = ImageHeader::new(2, 5, 8, ColorType::Rgb, false).unwrap();
+        let mut raw: Vec<u8> = Vec::new();
+        for filter in 0..5u8 {
+            raw.push(filter);
+            raw.extend((0..6u8).map(|i| filter * 40 + i * 7 + 1));
+        }
+
+        let image = decode(&header, &[], &raw).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 5));
+        assert_eq!(image.row_bytes(), 6);
+        assert_eq!(
+            image.pixels(),
+            [
+                1, 8, 15, 22, 29, 36, 41, 48, 55, 103, 117, 131, 122, 136, 150, 205, 226, 247, 182,
+                196, 210, 79, 104, 128, 87, 108, 129, 5, 37, 68
+            ]
+        );
+        assert_eq!(image.sample(1, 4, 2), 68);
+        assert_eq!(&image.to_rgba8()[0..8], [1, 8, 15, 255, 22, 29, 36, 255]);
+    }
+
+    #[test]
+    pub fn test_image_sub_byte_depths() {
+        let header = ImageHeader::new(10, 2, 1, ColorType::Grayscale, false).unwrap();
+        let image = decode(&header, &[], &[0, 0b1011_0011, 0b0100_0000, 0, 0xFF, 0xC0]).unwrap();
+        let first_row: Vec<u16> = (0..10).map(|x| image.sample(x, 0, 0)).collect();
+        assert_eq!(first_row, [1, 0, 1, 1, 0, 0, 1, 1, 0, 1]);
+        assert!((0..10).all(|x| image.sample(x, 1, 0) == 1));
+        assert_eq!(&image.to_rgba8()[0..8], [255, 255, 255, 255, 0, 0, 0, 255]);
+
+        let header = ImageHeader::new(4, 1, 2, ColorType::Indexed, false).unwrap();
+        let palette = chunk("PLTE", &[255, 0, 0, 0, 255, 0, 0, 0, 255, 9, 9, 9]);
+        let trns = chunk("tRNS", &[0, 128]);
+        let image = decode(&header, &[palette, trns], &[0, 0b0001_1011]).unwrap();
+        assert_eq!(image.palette().unwrap().len(), 4);
+        assert_eq!(
+            image.transparency(),
+            Some(&Transparency::Palette(vec![0, 128]))
+        );
+        assert_eq!(
+            image.to_rgba8(),
+            [255, 0, 0, 0, 0, 255, 0, 128, 0, 0, 255, 255, 9, 9, 9, 255]
+        );
+    }
+
+    #[test]
+    pub fn test_image_sixteen_bit() {
+        let header = ImageHeader::new(2, 1, 16, ColorType::Grayscale, false).unwrap();
+        let trns = chunk("tRNS", &[0x12, 0x34]);
+        let image = decode(&header, &[trns], &[0, 0x12, 0x34, 0xAB, 0xCD]).unwrap();
+        assert_eq!(image.sample(0, 0, 0), 0x1234);
+        assert_eq!(image.sample(1, 0, 0), 0xABCD);
+        assert_eq!(image.transparency(), Some(&Transparency::Gray(0x1234)));
+        assert_eq!(
+            image.to_rgba8(),
+            [0x12, 0x12, 0x12, 0, 0xAB, 0xAB, 0xAB, 255]
+        );
+
+        let header = ImageHeader::new(1, 1, 16, ColorType::Rgba, false).unwrap();
+        let image = decode(&header, &[], &[0, 1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        assert_eq!(image.sample(0, 0, 3), 0x0708);
+        assert_eq!(image.to_rgba8(), [1, 3, 5, 7]);
+    }
+
+    #[test]
+    pub fn test_image_interlaced() {
+        for (color_type, bit_depth) in [
+            (ColorType::Grayscale, 1),
+            (ColorType::Indexed, 4),
+            (ColorType::Rgba, 8),
+            (ColorType::Rgb, 16),
+        ] {
+            for (width, height) in [(1, 1), (3, 2), (9, 9), (13, 7)] {
+                let header = ImageHeader::new(width, height, bit_depth, color_type, false).unwrap();
+                let size = image_size(width, height, header.bits_per_pixel()).unwrap();
+                let pixels: Vec<u8> = (0..size).map(|i| (i * 37 % 251) as u8).collect();
+                let image = Image::new(width, height, color_type, bit_depth, pixels).unwrap();
+
+                let row_len = image.row_bytes();
+                let raw: Vec<u8> = image
+                    .pixels()
+                    .chunks(row_len)
+                    .flat_map(|row| [0].into_iter().chain(row.iter().copied()))
+                    .collect();
+                let palette = chunk("PLTE", &[0; 48]);
+                let chunks = if color_type == ColorType::Indexed {
+                    vec![palette]
+                } else {
+                    Vec::new()
+                };
+                let plain = decode(&header, &chunks, &raw).unwrap();
+                assert_eq!(plain.pixels(), image.pixels());
+
+                let header = ImageHeader::new(width, height, bit_depth, color_type, true).unwrap();
+                let interlaced = decode(&header, &chunks, &interlace(&image, &header)).unwrap();
+                // compared sample by sample, the padding bits of sub-byte rows are not carried over
+                assert_eq!(
+                    samples(&interlaced),
+                    samples(&plain),
+                    "{} {}x{}",
+                    color_type,
+                    width,
+                    height
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_image_decode_errors() {
+        let header = ImageHeader::new(2, 2, 8, ColorType::Grayscale, false).unwrap();
+        assert!(matches!(
+            decode(&header, &[], &[0, 1, 2, 5, 3, 4]),
+            Err(Error::InvalidFilterType(5))
+        ));
+        assert!(matches!(
+            decode(&header, &[], &[0, 1, 2, 0, 3]),
+            Err(Error::ImageDataLength {
+                expected: 6,
+                actual: 5
+            })
+        ));
+        assert!(matches!(
+            decode(&header, &[], &[0, 1, 2, 0, 3, 4, 0]),
+            Err(Error::OutputLimitExceeded(6))
+        ));
+        assert!(matches!(
+            Image::from_png(&build_png(&header, &[], &[0, 1, 2, 0, 3, 4]), 5),
+            Err(Error::OutputLimitExceeded(5))
+        ));
+        assert!(matches!(
+            decode(&header, &[chunk("tRNS", &[0, 1, 2])], &[0, 1, 2, 0, 3, 4]),
+            Err(Error::MalformedChunk { .. })
+        ));
+
+        let header = ImageHeader::new(2, 2, 8, ColorType::Indexed, false).unwrap();
+        assert!(matches!(
+            decode(&header, &[], &[0, 1, 2, 0, 3, 4]),
+            Err(Error::ChunkNotFound(_))
+        ));
+        assert!(matches!(
+            decode(&header, &[chunk("PLTE", &[1, 2])], &[0, 1, 2, 0, 3, 4]),
+            Err(Error::MalformedChunk { .. })
+        ));
+        let trns = chunk("tRNS", &[0, 0]);
+        assert!(matches!(
+            decode(
+                &header,
+                &[chunk("PLTE", &[1, 2, 3]), trns],
+                &[0, 1, 2, 0, 3, 4]
+            ),
+            Err(Error::MalformedChunk { .. })
+        ));
+
+        let header = ImageHeader::new(1 << 30, 1 << 30, 16, ColorType::Rgba, false).unwrap();
+        assert!(Image::from_png(&build_png(&header, &[], &[]), DEFAULT_OUTPUT_LIMIT).is_err());
+        assert!(matches!(
+            Image::new(2, 2, ColorType::Rgb, 8, vec![0; 11]),
+            Err(Error::ImageDataLength {
+                expected: 12,
+                actual: 11
+            })
+        ));
+    }
+
+    #[test]
+    pub fn test_image_encode_round_trip() {
+        for (color_type, bit_depth) in [
+            (ColorType::Grayscale, 1),
+            (ColorType::Grayscale, 2),
+            (ColorType::Grayscale, 4),
+            (ColorType::Grayscale, 8),
+            (ColorType::Grayscale, 16),
+            (ColorType::Rgb, 8),
+            (ColorType::Rgb, 16),
+            (ColorType::Indexed, 1),
+            (ColorType::Indexed, 8),
+            (ColorType::GrayscaleAlpha, 8),
+            (ColorType::GrayscaleAlpha, 16),
+            (ColorType::Rgba, 8),
+            (ColorType::Rgba, 16),
+        ] {
+            let (width, height) = (13, 7);
+            let size = image_size(width, height, color_type.channels() * bit_depth).unwrap();
+            let pixels: Vec<u8> = (0..size).map(|i| (i * i / 7 % 256) as u8).collect();
+            let mut image = Image::new(width, height, color_type, bit_depth as u8, pixels).unwrap();
+            if color_type == ColorType::Indexed {
+                image.set_palette(vec![[1, 2, 3]; 256]).unwrap();
+                image
+                    .set_transparency(Transparency::Palette(vec![0, 64, 128]))
+                    .unwrap();
+            }
+
+            let png = image.to_png(CompressionLevel::Default).unwrap();
+            assert!(png.validate().is_empty());
+            let decoded = Image::from_png(&Png::from_bytes(&png.bytes()).unwrap(), 1 << 20);
+            assert_eq!(decoded.unwrap(), image, "{} {}", color_type, bit_depth);
+        }
+    }
+
+    #[test]
+    pub fn test_image_encode_chunks() {
+        let mut image = Image::new(2, 1, ColorType::Rgb, 8, vec![1, 2, 3, 4, 5, 6]).unwrap();
+        image.set_palette(vec![[1, 2, 3], [4, 5, 6]]).unwrap();
+        image.set_transparency(Transparency::Rgb(1, 2, 3)).unwrap();
+        let png = image.to_png(CompressionLevel::Fast).unwrap();
+        let types: Vec<&str> = png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().str())
+            .collect();
+        assert_eq!(types, ["IHDR", "PLTE", "tRNS", "IDAT", "IEND"]);
+
+        // incompressible pixels spill over several IDAT chunks
+        let pixels: Vec<u8> = (0..(200 * 200 * 3))
+            .map(|i: u32| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let image = Image::new(200, 200, ColorType::Rgb, 8, pixels).unwrap();
+        let png = image.to_png(CompressionLevel::Stored).unwrap();
+        let idat_count = png
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().str() == "IDAT")
+            .count();
+        assert!(idat_count > 1);
+        assert_eq!(Image::from_png(&png, 1 << 20).unwrap(), image);
+    }
+
+    #[test]
+    pub fn test_image_encode_errors() {
+        let mut image = Image::new(1, 1, ColorType::Grayscale, 4, vec![0]).unwrap();
+        assert!(matches!(
+            image.set_palette(vec![[0, 0, 0]]),
+            Err(Error::MalformedChunk { .. })
+        ));
+        assert!(matches!(
+            image.set_transparency(Transparency::Rgb(0, 0, 0)),
+            Err(Error::MalformedChunk { .. })
+        ));
+        assert!(matches!(
+            image.set_transparency(Transparency::Gray(16)),
+            Err(Error::MalformedChunk { .. })
+        ));
+        assert!(image.set_transparency(Transparency::Gray(15)).is_ok());
+
+        let mut image = Image::new(1, 1, ColorType::Indexed, 8, vec![0]).unwrap();
+        assert!(matches!(
+            image.to_png(CompressionLevel::Fast),
+            Err(Error::ChunkNotFound(_))
+        ));
+        assert!(matches!(
+            image.set_palette(Vec::new()),
+            Err(Error::MalformedChunk { .. })
+        ));
+        image.set_palette(vec![[0, 0, 0]]).unwrap();
+        assert!(matches!(
+            image.set_transparency(Transparency::Palette(vec![0, 0])),
+            Err(Error::MalformedChunk { .. })
+        ));
+    }
+}
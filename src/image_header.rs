@@ -0,0 +1,299 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::error::{Error, Result};
+use crate::json::Json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => return Ok(Self::Grayscale),
+            2 => return Ok(Self::Rgb),
+            3 => return Ok(Self::Indexed),
+            4 => return Ok(Self::GrayscaleAlpha),
+            6 => return Ok(Self::Rgba),
+            _ => return Err(Error::InvalidColorType(value)),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::Grayscale => return 0,
+            Self::Rgb => return 2,
+            Self::Indexed => return 3,
+            Self::GrayscaleAlpha => return 4,
+            Self::Rgba => return 6,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::Grayscale | Self::Indexed => return 1,
+            Self::GrayscaleAlpha => return 2,
+            Self::Rgb => return 3,
+            Self::Rgba => return 4,
+        }
+    }
+
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            Self::Grayscale => return &[1, 2, 4, 8, 16],
+            Self::Indexed => return &[1, 2, 4, 8],
+            Self::Rgb | Self::GrayscaleAlpha | Self::Rgba => return &[8, 16],
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Grayscale => "Grayscale",
+            Self::Rgb => "RGB",
+            Self::Indexed => "Indexed",
+            Self::GrayscaleAlpha => "GrayscaleAlpha",
+            Self::Rgba => "RGBA",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageHeader {
+    m_width: u32,
+    m_height: u32,
+    m_bit_depth: u8,
+    m_color_type: ColorType,
+    m_interlaced: bool,
+}
+
+impl ImageHeader {
+    pub const CHUNK_TYPE: &'static str = "IHDR";
+    const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
+    pub fn new(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: ColorType,
+        interlaced: bool,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 || width > Self::MAX_DIMENSION || height > Self::MAX_DIMENSION
+        {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(Error::InvalidBitDepth {
+                color_type: color_type.value(),
+                bit_depth,
+            });
+        }
+
+        return Ok(Self {
+            m_width: width,
+            m_height: height,
+            m_bit_depth: bit_depth,
+            m_color_type: color_type,
+            m_interlaced: interlaced,
+        });
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type().str() != Self::CHUNK_TYPE {
+            return Err(Error::UnexpectedChunkType {
+                expected: String::from(Self::CHUNK_TYPE),
+                found: String::from(chunk.chunk_type().str()),
+            });
+        }
+
+        let data = chunk.data();
+        if data.len() != 13 {
+            return Err(Error::MalformedChunk {
+                chunk_type: String::from(Self::CHUNK_TYPE),
+                reason: format!("expected 13 bytes of data, found {}", data.len()),
+            });
+        }
+
+        // width(4 bytes) + height(4 bytes) + bit_depth + color_type + compression + filter + interlace
+        let width = u32::from_be_bytes(data[0..4].try_into().unwrap());
+        let height = u32::from_be_bytes(data[4..8].try_into().unwrap());
+        let color_type = ColorType::from_u8(data[9])?;
+        if data[10] != 0 {
+            return Err(Error::InvalidCompressionMethod(data[10]));
+        }
+        if data[11] != 0 {
+            return Err(Error::InvalidFilterMethod(data[11]));
+        }
+        let interlaced = match data[12] {
+            0 => false,
+            1 => true,
+            method => return Err(Error::InvalidInterlaceMethod(method)),
+        };
+
+        return Self::new(width, height, data[8], color_type, interlaced);
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data: Vec<u8> = Vec::with_capacity(13);
+        data.extend_from_slice(&self.m_width.to_be_bytes());
+        data.extend_from_slice(&self.m_height.to_be_bytes());
+        data.push(self.m_bit_depth);
+        data.push(self.m_color_type.value());
+        data.push(0);
+        data.push(0);
+        data.push(self.m_interlaced as u8);
+
+        return Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE)?, data);
+    }
+
+    pub fn width(&self) -> u32 {
+        return self.m_width;
+    }
+
+    pub fn height(&self) -> u32 {
+        return self.m_height;
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        return self.m_bit_depth;
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        return self.m_color_type;
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        return self.m_interlaced;
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        return self.m_color_type.channels() * self.m_bit_depth as usize;
+    }
+
+    pub fn to_json(&self) -> Json {
+        return Json::object(vec![
+            ("width", Json::Number(self.width() as u64)),
+            ("height", Json::Number(self.height() as u64)),
+            ("bit_depth", Json::Number(self.bit_depth() as u64)),
+            ("color_type", Json::String(self.color_type().to_string())),
+            ("interlaced", Json::Bool(self.is_interlaced())),
+        ]);
+    }
+}
+
+impl fmt::Display for ImageHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ImageHeader: {{ Width: {}, Height: {}, BitDepth: {}, ColorType: {}, Interlaced: {}}}",
+            self.width(),
+            self.height(),
+            self.bit_depth(),
+            self.color_type(),
+            self.is_interlaced()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_chunk(data: &[u8]) -> Chunk {
+        return Chunk::new(ChunkType::from_str("IHDR").unwrap(), data.to_vec()).unwrap();
+    }
+
+    #[test]
+    pub fn test_image_header_from_chunk() {
+        let chunk = ihdr_chunk(&[0, 0, 0, 50, 0, 0, 0, 40, 8, 6, 0, 0, 1]);
+        let header = ImageHeader::from_chunk(&chunk).unwrap();
+
+        assert_eq!(header.width(), 50);
+        assert_eq!(header.height(), 40);
+        assert_eq!(header.bit_depth(), 8);
+        assert_eq!(header.color_type(), ColorType::Rgba);
+        assert!(header.is_interlaced());
+        assert_eq!(header.bits_per_pixel(), 32);
+        assert_eq!(header.to_chunk().unwrap(), chunk);
+    }
+
+    #[test]
+    pub fn test_image_header_bit_depths() {
+        for (color_type, bit_depth, valid) in [
+            (0, 1, true),
+            (0, 16, true),
+            (0, 3, false),
+            (2, 8, true),
+            (2, 4, false),
+            (3, 8, true),
+            (3, 16, false),
+            (4, 16, true),
+            (4, 1, false),
+            (6, 8, true),
+            (6, 2, false),
+        ] {
+            let chunk = ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, bit_depth, color_type, 0, 0, 0]);
+            let header_res = ImageHeader::from_chunk(&chunk);
+            if valid {
+                assert!(header_res.is_ok());
+            } else {
+                assert!(matches!(header_res, Err(Error::InvalidBitDepth { .. })));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_image_header_validation() {
+        let chunk = ihdr_chunk(&[0, 0, 0, 0, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        assert!(matches!(
+            ImageHeader::from_chunk(&chunk),
+            Err(Error::InvalidDimensions { .. })
+        ));
+
+        let chunk = ihdr_chunk(&[128, 0, 0, 0, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        assert!(matches!(
+            ImageHeader::from_chunk(&chunk),
+            Err(Error::InvalidDimensions { .. })
+        ));
+
+        let chunk = ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 5, 0, 0, 0]);
+        assert!(matches!(
+            ImageHeader::from_chunk(&chunk),
+            Err(Error::InvalidColorType(5))
+        ));
+
+        let chunk = ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 1, 0, 0]);
+        assert!(matches!(
+            ImageHeader::from_chunk(&chunk),
+            Err(Error::InvalidCompressionMethod(1))
+        ));
+
+        let chunk = ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 1, 0]);
+        assert!(matches!(
+            ImageHeader::from_chunk(&chunk),
+            Err(Error::InvalidFilterMethod(1))
+        ));
+
+        let chunk = ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 2]);
+        assert!(matches!(
+            ImageHeader::from_chunk(&chunk),
+            Err(Error::InvalidInterlaceMethod(2))
+        ));
+
+        let chunk = ihdr_chunk(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0]);
+        assert!(matches!(
+            ImageHeader::from_chunk(&chunk),
+            Err(Error::MalformedChunk { .. })
+        ));
+    }
+}
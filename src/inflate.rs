@@ -0,0 +1,442 @@
+use crate::error::{Error, Result};
+
+// default cap on decompressed output, to defend against decompression bombs
+pub const DEFAULT_OUTPUT_LIMIT: usize = 256 * 1024 * 1024;
+
+const MAX_BITS: usize = 15;
+
+// base lengths and extra bits for length symbols 257..285
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+// base distances and extra bits for distance symbols 0..29
+pub(crate) const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(crate) const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// order in which code length code lengths are stored in a dynamic block header
+pub(crate) const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitReader<'a> {
+    m_data: &'a [u8],
+    m_pos: usize,
+    m_bit_buf: u64,
+    m_bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        return Self {
+            m_data: data,
+            m_pos: 0,
+            m_bit_buf: 0,
+            m_bit_count: 0,
+        };
+    }
+
+    // deflate packs bits starting from the least significant bit of each byte
+    fn bits(&mut self, n: u32) -> Result<u32> {
+        while self.m_bit_count < n {
+            if self.m_pos >= self.m_data.len() {
+                return Err(Error::InvalidDeflateData("unexpected end of stream"));
+            }
+            self.m_bit_buf |= (self.m_data[self.m_pos] as u64) << self.m_bit_count;
+            self.m_pos += 1;
+            self.m_bit_count += 8;
+        }
+
+        let value = (self.m_bit_buf & ((1u64 << n) - 1)) as u32;
+        self.m_bit_buf >>= n;
+        self.m_bit_count -= n;
+        return Ok(value);
+    }
+
+    // drops the remaining bits of the current byte
+    fn align(&mut self) {
+        let extra = self.m_bit_count % 8;
+        self.m_bit_buf >>= extra;
+        self.m_bit_count -= extra;
+    }
+
+    // byte position just after the last consumed bit, once aligned
+    fn byte_pos(&self) -> usize {
+        return self.m_pos - (self.m_bit_count / 8) as usize;
+    }
+}
+
+// canonical Huffman code stored as per-length counts and symbols sorted by code
+struct Huffman {
+    m_counts: [u16; MAX_BITS + 1],
+    m_symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut m_counts = [0u16; MAX_BITS + 1];
+        for len in lengths {
+            m_counts[*len as usize] += 1;
+        }
+
+        // reject over-subscribed codes; incomplete codes fail only if a missing code is hit
+        let mut left: i32 = 1;
+        for count in m_counts.iter().skip(1) {
+            left <<= 1;
+            left -= *count as i32;
+            if left < 0 {
+                return Err(Error::InvalidDeflateData("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + m_counts[len];
+        }
+
+        let mut m_symbols = vec![0u16; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                m_symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        return Ok(Self {
+            m_counts,
+            m_symbols,
+        });
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.m_counts[len] as i32;
+            if code - first < count {
+                return Ok(self.m_symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        return Err(Error::InvalidDeflateData("invalid Huffman code"));
+    }
+}
+
+// decompresses a raw DEFLATE stream, returning the output and the number of input bytes consumed
+pub fn inflate(data: &[u8], limit: usize) -> Result<(Vec<u8>, usize)> {
+    let mut reader = BitReader::new(data);
+    let mut out: Vec<u8> = Vec::new();
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => inflate_stored(&mut reader, &mut out, limit)?,
+            1 => {
+                let (lit_len, dist) = fixed_codes()?;
+                inflate_codes(&mut reader, &mut out, limit, &lit_len, &dist)?;
+            }
+            2 => {
+                let (lit_len, dist) = dynamic_codes(&mut reader)?;
+                inflate_codes(&mut reader, &mut out, limit, &lit_len, &dist)?;
+            }
+            _ => return Err(Error::InvalidDeflateData("invalid block type")),
+        }
+
+        if last {
+            break;
+        }
+    }
+
+    reader.align();
+    return Ok((out, reader.byte_pos()));
+}
+
+// decompresses a zlib stream (RFC 1950) and verifies its Adler-32 checksum
+pub fn zlib_decompress(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    if data.len() < 2 {
+        return Err(Error::InvalidZlibHeader);
+    }
+
+    // compression method 8 (deflate), window size up to 32K, header check bits, no preset dictionary
+    let cmf = data[0];
+    let flg = data[1];
+    if cmf & 0x0F != 8
+        || cmf >> 4 > 7
+        || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31)
+        || flg & 0x20 != 0
+    {
+        return Err(Error::InvalidZlibHeader);
+    }
+
+    let (out, consumed) = inflate(&data[2..], limit)?;
+    let end = 2 + consumed;
+    if data.len() < end + 4 {
+        return Err(Error::InvalidDeflateData("missing Adler-32 checksum"));
+    }
+
+    let expected = u32::from_be_bytes(data[end..(end + 4)].try_into().unwrap());
+    let actual = adler32(&out);
+    if expected != actual {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+
+    return Ok(out);
+}
+
+pub fn adler32(bytes: &[u8]) -> u32 {
+    // largest n such that 255n(n+1)/2 + (n+1)(65520) fits in 32 bits
+    const NMAX: usize = 5552;
+    const MOD: u32 = 65521;
+
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for block in bytes.chunks(NMAX) {
+        for byte in block {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+
+    return (b << 16) | a;
+}
+
+fn check_limit(out_len: usize, len: usize, limit: usize) -> Result<()> {
+    if out_len + len > limit {
+        return Err(Error::OutputLimitExceeded(limit));
+    }
+    return Ok(());
+}
+
+fn inflate_stored(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<()> {
+    reader.align();
+    let len = reader.bits(16)?;
+    let nlen = reader.bits(16)?;
+    if len != !nlen & 0xFFFF {
+        return Err(Error::InvalidDeflateData("stored block length mismatch"));
+    }
+
+    let len = len as usize;
+    let start = reader.byte_pos();
+    if start + len > reader.m_data.len() {
+        return Err(Error::InvalidDeflateData("unexpected end of stream"));
+    }
+    check_limit(out.len(), len, limit)?;
+    out.extend_from_slice(&reader.m_data[start..(start + len)]);
+
+    // the bit buffer is empty after `align` and the two 16-bit reads
+    reader.m_pos = start + len;
+    reader.m_bit_buf = 0;
+    reader.m_bit_count = 0;
+    return Ok(());
+}
+
+fn inflate_codes(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    limit: usize,
+    lit_len: &Huffman,
+    dist: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = lit_len.decode(reader)? as usize;
+        if symbol < 256 {
+            check_limit(out.len(), 1, limit)?;
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let symbol = symbol - 257;
+            if symbol >= LENGTH_BASE.len() {
+                return Err(Error::InvalidDeflateData("invalid length symbol"));
+            }
+            let len =
+                LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+            let symbol = dist.decode(reader)? as usize;
+            if symbol >= DIST_BASE.len() {
+                return Err(Error::InvalidDeflateData("invalid distance symbol"));
+            }
+            let distance =
+                DIST_BASE[symbol] as usize + reader.bits(DIST_EXTRA[symbol] as u32)? as usize;
+            if distance > out.len() {
+                return Err(Error::InvalidDeflateData("distance too far back"));
+            }
+
+            check_limit(out.len(), len, limit)?;
+            let start = out.len() - distance;
+            for i in 0..len {
+                out.push(out[start + i]);
+            }
+        }
+    }
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[0..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..288].fill(8);
+
+    return Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?));
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman)> {
+    let hlit = reader.bits(5)? as usize + 257;
+    let hdist = reader.bits(5)? as usize + 1;
+    let hclen = reader.bits(4)? as usize + 4;
+    if hlit > 286 || hdist > 30 {
+        return Err(Error::InvalidDeflateData(
+            "too many length or distance codes",
+        ));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(hclen) {
+        code_lengths[*i] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    // literal/length and distance code lengths share one run-length encoded sequence
+    let mut lengths: Vec<u8> = Vec::with_capacity(hlit + hdist);
+    while lengths.len() < hlit + hdist {
+        let symbol = code_length_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(last) => (*last, 3 + reader.bits(2)? as usize),
+                None => return Err(Error::InvalidDeflateData("repeat with no previous length")),
+            },
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if lengths.len() + repeat > hlit + hdist {
+            return Err(Error::InvalidDeflateData("too many code lengths"));
+        }
+        lengths.extend(std::iter::repeat_n(value, repeat));
+    }
+
+    if lengths[256] == 0 {
+        return Err(Error::InvalidDeflateData("missing end-of-block code"));
+    }
+
+    let lit_len = Huffman::new(&lengths[..hlit])?;
+    let dist = Huffman::new(&lengths[hlit..])?;
+    return Ok((lit_len, dist));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS: &[u8] = b"In the beginning God created the heaven and the earth. And the earth was without form, and void; and darkness was upon the face of the deep.";
+
+    #[test]
+    pub fn test_zlib_decompress_blocks() {
+        // fixed Huffman block
+        let data = [120, 156, 203, 72, 205, 201, 201, 7, 0, 6, 44, 2, 21];
+        assert_eq!(zlib_decompress(&data, 1024).unwrap(), b"hello");
+
+        // stored block
+        #[rustfmt::skip]
+        let data = [
+            120, 1, 1, 23, 0, 232, 255, 104, 101, 108, 108, 111, 32, 104, 101, 108, 108, 111, 32,
+            104, 101, 108, 108, 111, 32, 104, 101, 108, 108, 111, 104, 3, 8, 177,
+        ];
+        assert_eq!(
+            zlib_decompress(&data, 1024).unwrap(),
+            b"hello hello hello hello"
+        );
+
+        // fixed Huffman block with back references
+        let data = [120, 1, 75, 76, 74, 78, 196, 64, 0, 88, 117, 8, 11];
+        assert_eq!(
+            zlib_decompress(&data, 1024).unwrap(),
+            b"abcabcabcabcabcabcabc"
+        );
+
+        // dynamic Huffman block
+        #[rustfmt::skip]
+        let data = [
+            120, 218, 77, 141, 193, 9, 195, 64, 12, 4, 91, 217, 2, 140, 27, 200, 203, 175, 144, 50,
+            20, 107, 207, 119, 132, 72, 230, 78, 182, 219, 15, 200, 159, 252, 102, 216, 129, 125,
+            25, 162, 18, 111, 110, 205, 172, 217, 134, 167, 43, 214, 78, 9, 106, 46, 149, 114, 210,
+            32, 118, 43, 165, 71, 157, 177, 252, 43, 46, 25, 184, 90, 84, 63, 2, 197, 251, 119,
+            202, 252, 244, 166, 143, 36, 149, 254, 49, 142, 145, 225, 177, 251, 253, 89, 100, 37,
+            188, 36, 43, 185, 207, 63, 162, 17, 49, 94,
+        ];
+        assert_eq!(zlib_decompress(&data, 1024).unwrap(), GENESIS);
+    }
+
+    #[test]
+    pub fn test_zlib_decompress_errors() {
+        let data = [120, 156, 203, 72, 205, 201, 201, 7, 0, 6, 44, 2, 21];
+
+        let mut bad_header = data;
+        bad_header[1] = 157;
+        assert!(matches!(
+            zlib_decompress(&bad_header, 1024),
+            Err(Error::InvalidZlibHeader)
+        ));
+
+        let mut bad_checksum = data;
+        bad_checksum[12] = 22;
+        assert!(matches!(
+            zlib_decompress(&bad_checksum, 1024),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+
+        assert!(matches!(
+            zlib_decompress(&data[..8], 1024),
+            Err(Error::InvalidDeflateData(_))
+        ));
+        assert!(matches!(
+            zlib_decompress(&data, 4),
+            Err(Error::OutputLimitExceeded(4))
+        ));
+
+        // block type 3 is reserved
+        assert!(matches!(
+            inflate(&[0b111], 1024),
+            Err(Error::InvalidDeflateData(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_inflate_garbage_does_not_panic() {
+        let mut seed: u32 = 0x9e37_79b9;
+        for len in 0..512 {
+            let data: Vec<u8> = (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    return (seed >> 16) as u8;
+                })
+                .collect();
+            let _ = inflate(&data, 1 << 16);
+        }
+    }
+
+    #[test]
+    pub fn test_adler32() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+        assert_eq!(adler32(&[0xFF; 100_000]), 0x149A_302C);
+    }
+}
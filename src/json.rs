@@ -0,0 +1,153 @@
+use std::fmt;
+use std::fmt::Write;
+
+// minimal JSON document model, enough for reporting on PNG files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    // keys keep their insertion order
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(fields: Vec<(&str, Json)>) -> Self {
+        return Self::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (String::from(key), value))
+                .collect(),
+        );
+    }
+
+    pub fn string(s: &str) -> Self {
+        return Self::String(String::from(s));
+    }
+
+    // single-line form, e.g. for NDJSON
+    pub fn to_compact(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, None, 0);
+        return out;
+    }
+
+    // indented by two spaces per level
+    pub fn to_pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(2), 0);
+        return out;
+    }
+
+    fn write(&self, out: &mut String, indent: Option<usize>, depth: usize) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Self::Number(value) => out.push_str(&value.to_string()),
+            Self::String(value) => write_string(out, value),
+            Self::Array(values) => {
+                write_items(out, ('[', ']'), values.len(), indent, depth, |out, i| {
+                    values[i].write(out, indent, depth + 1);
+                });
+            }
+            Self::Object(fields) => {
+                write_items(out, ('{', '}'), fields.len(), indent, depth, |out, i| {
+                    let (key, value) = &fields[i];
+                    write_string(out, key);
+                    out.push_str(if indent.is_some() { ": " } else { ":" });
+                    value.write(out, indent, depth + 1);
+                });
+            }
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_compact())
+    }
+}
+
+fn write_items<F>(
+    out: &mut String,
+    (open, close): (char, char),
+    len: usize,
+    indent: Option<usize>,
+    depth: usize,
+    mut write_item: F,
+) where
+    F: FnMut(&mut String, usize),
+{
+    out.push(open);
+    for i in 0..len {
+        if i > 0 {
+            out.push(',');
+        }
+        if let Some(indent) = indent {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent * (depth + 1)));
+        }
+        write_item(out, i);
+    }
+    if let (Some(indent), true) = (indent, len > 0) {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent * depth));
+    }
+    out.push(close);
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_json_compact() {
+        let json = Json::object(vec![
+            ("name", Json::string("a \"b\"\n\u{1}")),
+            ("n", Json::Number(42)),
+            ("ok", Json::Bool(true)),
+            ("none", Json::Null),
+            (
+                "list",
+                Json::Array(vec![Json::Number(1), Json::Array(vec![])]),
+            ),
+        ]);
+        assert_eq!(
+            json.to_compact(),
+            r#"{"name":"a \"b\"\n\u0001","n":42,"ok":true,"none":null,"list":[1,[]]}"#
+        );
+        assert_eq!(json.to_string(), json.to_compact());
+    }
+
+    #[test]
+    pub fn test_json_pretty() {
+        let json = Json::object(vec![
+            ("list", Json::Array(vec![Json::Number(1), Json::Number(2)])),
+            ("empty", Json::Object(vec![])),
+        ]);
+        assert_eq!(
+            json.to_pretty(),
+            "{\n  \"list\": [\n    1,\n    2\n  ],\n  \"empty\": {}\n}"
+        );
+    }
+}
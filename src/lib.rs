@@ -0,0 +1,30 @@
+#![allow(clippy::needless_return)]
+
+pub mod apng;
+pub mod chunk;
+pub mod chunk_ref;
+pub mod chunk_type;
+pub mod crypto;
+pub mod deflate;
+pub mod encoding;
+pub mod error;
+mod filter;
+pub mod image;
+pub mod image_header;
+pub mod inflate;
+pub mod json;
+pub mod lsb;
+pub mod ops;
+pub mod png;
+pub mod reader;
+mod repair;
+pub mod text_chunk;
+mod validation;
+pub mod writer;
+
+pub use chunk::Chunk;
+pub use chunk_type::ChunkType;
+pub use error::{Error, Result};
+pub use png::Png;
+pub use repair::{CrcMismatch, CrcRepair};
+pub use validation::{Placement, Violation};
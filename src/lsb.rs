@@ -0,0 +1,461 @@
+use crate::error::{Error, Result};
+use crate::image::Image;
+use crate::image_header::ColorType;
+
+// magic(2 bytes) + version + payload length(4 bytes), written in front of the payload
+const MAGIC: [u8; 2] = *b"PM";
+const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 7;
+const MAX_BITS_PER_SAMPLE: u8 = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsbOptions {
+    // channel letters, `None` for every color channel
+    m_channels: Option<String>,
+    m_bits_per_sample: u8,
+}
+
+impl LsbOptions {
+    // `channels` is made of `r`, `g`, `b` and `a`, with `y` for the gray channel
+    pub fn new(channels: Option<&str>, bits_per_sample: u8) -> Result<Self> {
+        if bits_per_sample == 0 || bits_per_sample > MAX_BITS_PER_SAMPLE {
+            return Err(Error::InvalidLsbOptions(format!(
+                "bits per sample must be between 1 and {}, found {}",
+                MAX_BITS_PER_SAMPLE, bits_per_sample
+            )));
+        }
+        if let Some(channels) = channels {
+            if channels.is_empty() || channels.chars().any(|c| !"rgbay".contains(c)) {
+                return Err(Error::InvalidLsbOptions(format!(
+                    "invalid channels {:?}",
+                    channels
+                )));
+            }
+        }
+
+        return Ok(Self {
+            m_channels: channels.map(String::from),
+            m_bits_per_sample: bits_per_sample,
+        });
+    }
+
+    pub fn channels(&self) -> Option<&str> {
+        return self.m_channels.as_deref();
+    }
+
+    pub fn bits_per_sample(&self) -> u8 {
+        return self.m_bits_per_sample;
+    }
+
+    // sample indices within a pixel, in embedding order
+    fn channel_indices(&self, color_type: ColorType) -> Result<Vec<usize>> {
+        let letters = match color_type {
+            ColorType::Grayscale => "y",
+            ColorType::GrayscaleAlpha => "ya",
+            ColorType::Rgb => "rgb",
+            ColorType::Rgba => "rgba",
+            ColorType::Indexed => "",
+        };
+        let channels = match self.channels() {
+            Some(channels) => channels,
+            None => letters.trim_end_matches('a'),
+        };
+
+        let mut indices: Vec<usize> = Vec::new();
+        for c in channels.chars() {
+            match letters.find(c) {
+                Some(i) if !indices.contains(&i) => indices.push(i),
+                Some(_) => {
+                    return Err(Error::InvalidLsbOptions(format!(
+                        "channel {:?} is given twice",
+                        c
+                    )))
+                }
+                None => {
+                    return Err(Error::InvalidLsbOptions(format!(
+                        "color type {} has no channel {:?}",
+                        color_type, c
+                    )))
+                }
+            }
+        }
+
+        return Ok(indices);
+    }
+}
+
+impl Default for LsbOptions {
+    fn default() -> Self {
+        return Self {
+            m_channels: None,
+            m_bits_per_sample: 1,
+        };
+    }
+}
+
+// palette and sub-byte images cannot carry data in their samples without visible changes
+pub fn is_supported(image: &Image) -> bool {
+    return image.color_type() != ColorType::Indexed && image.bit_depth() >= 8;
+}
+
+// number of payload bytes `image` can carry, after the header
+pub fn capacity(image: &Image, options: &LsbOptions) -> Result<usize> {
+    let samples = sample_offsets(image, options)?.len();
+    return Ok(available(samples, options.bits_per_sample()));
+}
+
+// writes `payload` into the samples in scanline order
+pub fn embed(image: &mut Image, payload: &[u8], options: &LsbOptions) -> Result<()> {
+    let offsets = sample_offsets(image, options)?;
+    return write_samples(image, &offsets, payload, options.bits_per_sample());
+}
+
+pub fn extract(image: &Image, options: &LsbOptions) -> Result<Vec<u8>> {
+    let offsets = sample_offsets(image, options)?;
+    return read_samples(image, &offsets, options.bits_per_sample());
+}
+
+// writes `payload` into the samples in an order only `seed` reproduces, spreading it over the image
+pub fn embed_scattered(
+    image: &mut Image,
+    payload: &[u8],
+    options: &LsbOptions,
+    seed: &[u8; 32],
+) -> Result<()> {
+    let offsets = scattered_offsets(image, options, seed)?;
+    return write_samples(image, &offsets, payload, options.bits_per_sample());
+}
+
+pub fn extract_scattered(image: &Image, options: &LsbOptions, seed: &[u8; 32]) -> Result<Vec<u8>> {
+    let offsets = scattered_offsets(image, options, seed)?;
+    return read_samples(image, &offsets, options.bits_per_sample());
+}
+
+fn available(samples: usize, bits_per_sample: u8) -> usize {
+    return (samples * bits_per_sample as usize / 8).saturating_sub(HEADER_LEN);
+}
+
+fn write_samples(
+    image: &mut Image,
+    offsets: &[usize],
+    payload: &[u8],
+    bits_per_sample: u8,
+) -> Result<()> {
+    let available = available(offsets.len(), bits_per_sample);
+    if payload.len() > available || payload.len() > u32::MAX as usize {
+        return Err(Error::CapacityExceeded {
+            needed: payload.len(),
+            available,
+        });
+    }
+
+    let mut data: Vec<u8> = Vec::with_capacity(HEADER_LEN + payload.len());
+    data.extend_from_slice(&MAGIC);
+    data.push(VERSION);
+    data.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    data.extend_from_slice(payload);
+
+    let mut bits = data
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+        .peekable();
+    let mask = ((1u16 << bits_per_sample) - 1) as u8;
+    let pixels = image.pixels_mut();
+    for offset in offsets {
+        if bits.peek().is_none() {
+            break;
+        }
+
+        // the last sample is zero-padded if the data does not fill it
+        let mut value: u8 = 0;
+        for _ in 0..bits_per_sample {
+            value = (value << 1) | bits.next().unwrap_or(0);
+        }
+        pixels[*offset] = (pixels[*offset] & !mask) | value;
+    }
+
+    return Ok(());
+}
+
+fn read_samples(image: &Image, offsets: &[usize], bits_per_sample: u8) -> Result<Vec<u8>> {
+    let available = offsets.len() * bits_per_sample as usize / 8;
+    if available < HEADER_LEN {
+        return Err(Error::LsbPayloadNotFound);
+    }
+
+    let pixels = image.pixels();
+    let mut bits = offsets.iter().flat_map(|offset| {
+        (0..bits_per_sample)
+            .rev()
+            .map(move |i| (pixels[*offset] >> i) & 1)
+    });
+    let mut next_byte = || (0..8).fold(0u8, |byte, _| (byte << 1) | bits.next().unwrap_or(0));
+
+    let header: Vec<u8> = (0..HEADER_LEN).map(|_| next_byte()).collect();
+    if header[0..2] != MAGIC {
+        return Err(Error::LsbPayloadNotFound);
+    }
+    if header[2] != VERSION {
+        return Err(Error::UnsupportedLsbVersion(header[2]));
+    }
+    let len = u32::from_be_bytes(header[3..7].try_into().unwrap()) as usize;
+    if len > available - HEADER_LEN {
+        return Err(Error::LsbPayloadNotFound);
+    }
+
+    return Ok((0..len).map(|_| next_byte()).collect());
+}
+
+// sample offsets shuffled with a Fisher-Yates pass driven by `seed`
+fn scattered_offsets(image: &Image, options: &LsbOptions, seed: &[u8; 32]) -> Result<Vec<usize>> {
+    let mut offsets = sample_offsets(image, options)?;
+    let mut rng = Xoshiro256::new(seed);
+    for i in (1..offsets.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        offsets.swap(i, j);
+    }
+
+    return Ok(offsets);
+}
+
+// byte offsets of the selected samples; the low byte of 16-bit samples holds their LSBs
+fn sample_offsets(image: &Image, options: &LsbOptions) -> Result<Vec<usize>> {
+    if !is_supported(image) {
+        return Err(Error::UnsupportedLsbImage {
+            color_type: image.color_type().value(),
+            bit_depth: image.bit_depth(),
+        });
+    }
+
+    let indices = options.channel_indices(image.color_type())?;
+    let channels = image.color_type().channels();
+    let sample_len = image.bit_depth() as usize / 8;
+    let pixel_count = image.width() as usize * image.height() as usize;
+
+    let mut offsets: Vec<usize> = Vec::with_capacity(pixel_count * indices.len());
+    for pixel in 0..pixel_count {
+        for index in indices.iter() {
+            offsets.push((pixel * channels + index) * sample_len + sample_len - 1);
+        }
+    }
+
+    return Ok(offsets);
+}
+
+// xoshiro256** (Blackman and Vigna); a fast PRNG, the secrecy lies in the seed
+struct Xoshiro256 {
+    m_state: [u64; 4],
+}
+
+impl Xoshiro256 {
+    fn new(seed: &[u8; 32]) -> Self {
+        let mut m_state = [0u64; 4];
+        for (i, word) in seed.chunks(8).enumerate() {
+            m_state[i] = u64::from_le_bytes(word.try_into().unwrap());
+        }
+        // the all-zero state is a fixed point
+        if m_state == [0; 4] {
+            m_state[0] = 1;
+        }
+
+        return Self { m_state };
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.m_state;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+
+        return result;
+    }
+
+    // uniform in 0..n, by Lemire's multiply-shift method with rejection
+    fn below(&mut self, n: u64) -> u64 {
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let product = self.next_u64() as u128 * n as u128;
+            if product as u64 >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(color_type: ColorType, bit_depth: u8, width: u32, height: u32) -> Image {
+        let len = (width * height) as usize * color_type.channels() * bit_depth as usize / 8;
+        let pixels: Vec<u8> = (0..len).map(|i| (i * 7 % 256) as u8).collect();
+        return Image::new(width, height, color_type, bit_depth, pixels).unwrap();
+    }
+
+    #[test]
+    pub fn test_lsb_round_trip() {
+        let payload = b"This is where your secret message will be!";
+        for (color_type, bit_depth) in [
+            (ColorType::Grayscale, 8),
+            (ColorType::Grayscale, 16),
+            (ColorType::GrayscaleAlpha, 8),
+            (ColorType::Rgb, 8),
+            (ColorType::Rgb, 16),
+            (ColorType::Rgba, 8),
+        ] {
+            for bits_per_sample in [1, 2, 3, 8] {
+                let options = LsbOptions::new(None, bits_per_sample).unwrap();
+                let original = image(color_type, bit_depth, 40, 30);
+                let mut image = original.clone();
+                embed(&mut image, payload, &options).unwrap();
+                assert_eq!(extract(&image, &options).unwrap(), payload);
+
+                // only the selected low bits change
+                let mask = !(((1u16 << bits_per_sample) - 1) as u8);
+                let changed = original
+                    .pixels()
+                    .iter()
+                    .zip(image.pixels().iter())
+                    .filter(|(a, b)| *a & mask != *b & mask)
+                    .count();
+                assert_eq!(changed, 0);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_lsb_channels() {
+        let original = image(ColorType::Rgba, 8, 10, 10);
+
+        // alpha is left alone by default
+        let options = LsbOptions::default();
+        let mut image = original.clone();
+        embed(&mut image, &[0xFF; 20], &options).unwrap();
+        assert!((0..100).all(|i| image.pixels()[i * 4 + 3] == original.pixels()[i * 4 + 3]));
+        assert_eq!(
+            capacity(&image, &options).unwrap(),
+            100 * 3 / 8 - HEADER_LEN
+        );
+
+        let options = LsbOptions::new(Some("b"), 2).unwrap();
+        let mut image = original.clone();
+        embed(&mut image, b"blue", &options).unwrap();
+        for (i, (a, b)) in original.pixels().iter().zip(image.pixels()).enumerate() {
+            if i % 4 != 2 {
+                assert_eq!(a, b);
+            }
+        }
+        assert_eq!(extract(&image, &options).unwrap(), b"blue");
+        assert!(matches!(
+            extract(&image, &LsbOptions::default()),
+            Err(Error::LsbPayloadNotFound)
+        ));
+
+        assert!(matches!(
+            capacity(&image, &LsbOptions::new(Some("y"), 1).unwrap()),
+            Err(Error::InvalidLsbOptions(_))
+        ));
+        assert!(matches!(
+            capacity(&image, &LsbOptions::new(Some("rr"), 1).unwrap()),
+            Err(Error::InvalidLsbOptions(_))
+        ));
+        assert!(LsbOptions::new(Some("x"), 1).is_err());
+        assert!(LsbOptions::new(None, 0).is_err());
+        assert!(LsbOptions::new(None, 9).is_err());
+    }
+
+    #[test]
+    pub fn test_lsb_errors() {
+        let options = LsbOptions::default();
+        let mut image = image(ColorType::Rgb, 8, 8, 8);
+        assert_eq!(capacity(&image, &options).unwrap(), 17);
+        assert!(embed(&mut image, &[0; 17], &options).is_ok());
+        assert!(matches!(
+            embed(&mut image, &[0; 18], &options),
+            Err(Error::CapacityExceeded {
+                needed: 18,
+                available: 17
+            })
+        ));
+
+        let mut indexed = Image::new(2, 2, ColorType::Indexed, 8, vec![0; 4]).unwrap();
+        assert!(!is_supported(&indexed));
+        assert!(matches!(
+            embed(&mut indexed, b"", &options),
+            Err(Error::UnsupportedLsbImage { .. })
+        ));
+        let gray = Image::new(8, 1, ColorType::Grayscale, 1, vec![0]).unwrap();
+        assert!(matches!(
+            extract(&gray, &options),
+            Err(Error::UnsupportedLsbImage { .. })
+        ));
+
+        embed(&mut image, b"data", &options).unwrap();
+        // version byte lives in samples 16..24
+        image.pixels_mut()[22] ^= 1;
+        assert!(matches!(
+            extract(&image, &options),
+            Err(Error::UnsupportedLsbVersion(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_lsb_xoshiro() {
+        let mut seed = [0u8; 32];
+        for (i, word) in [1u64, 2, 3, 4].iter().enumerate() {
+            seed[(i * 8)..(i * 8 + 8)].copy_from_slice(&word.to_le_bytes());
+        }
+        let mut rng = Xoshiro256::new(&seed);
+        assert_eq!(rng.next_u64(), 11520);
+        assert_eq!(rng.next_u64(), 0);
+        assert_eq!(rng.next_u64(), 1509978240);
+
+        let mut rng = Xoshiro256::new(&[7; 32]);
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            counts[rng.below(6) as usize] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 850 && *count < 1150));
+    }
+
+    #[test]
+    pub fn test_lsb_scattered() {
+        let payload = b"This is where your secret message will be!";
+        let options = LsbOptions::default();
+        let original = image(ColorType::Rgb, 8, 64, 64);
+        let mut image = original.clone();
+        embed_scattered(&mut image, payload, &options, &[1; 32]).unwrap();
+        assert_eq!(
+            extract_scattered(&image, &options, &[1; 32]).unwrap(),
+            payload
+        );
+
+        // neither the wrong key nor the sequential mode find the payload
+        assert!(extract_scattered(&image, &options, &[2; 32]).is_err());
+        assert!(extract(&image, &options).is_err());
+
+        // the changed samples are spread over the whole image rather than its first rows
+        let changed: Vec<usize> = original
+            .pixels()
+            .iter()
+            .zip(image.pixels().iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| i)
+            .collect();
+        let len = image.pixels().len();
+        assert!(changed.iter().any(|i| *i < len / 4));
+        assert!(changed.iter().any(|i| *i > len * 3 / 4));
+
+        let mut small = Image::new(4, 4, ColorType::Rgb, 8, vec![0; 48]).unwrap();
+        assert!(matches!(
+            embed_scattered(&mut small, b"too long", &options, &[1; 32]),
+            Err(Error::CapacityExceeded { .. })
+        ));
+    }
+}
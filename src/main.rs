@@ -1,118 +1,731 @@
+#![allow(clippy::needless_return)]
+
 use std::env;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::process;
+
+use pngme::apng::{Animation, FrameControl};
+use pngme::chunk::ParseMode;
+use pngme::crypto;
+use pngme::deflate::CompressionLevel;
+use pngme::encoding::Encoding;
+use pngme::error::ErrorKind;
+use pngme::image::Image;
+use pngme::image_header::ColorType;
+use pngme::inflate;
+use pngme::json::Json;
+use pngme::lsb::{self, LsbOptions};
+use pngme::ops::{self, read_png, read_png_with_mode, save_png, WriteOptions, WriteReport};
+use pngme::png::{ChunkPosition, ChunkSelection};
+use pngme::text_chunk::{CompressedTextChunk, InternationalTextChunk, TextChunk, TextEntry};
+use pngme::{Error, Result};
+
+use crate::cli::{Command, Invocation, Matches};
+use crate::hexdump::hexdump;
 
-use crate::chunk::Chunk;
-use crate::chunk_type::ChunkType;
-use crate::png::Png;
+mod cli;
+mod hexdump;
 
-mod chunk;
-mod chunk_type;
-mod png;
+// bytes shown by `dump` unless told otherwise
+const DEFAULT_DUMP_LENGTH: usize = 256;
 
 fn read_args() -> Vec<String> {
-    return env::args().collect();
+    return env::args().skip(1).collect();
 }
 
-fn read_png(fname: &str) -> Result<Vec<u8>, String> {
-    let pname = Path::new(fname);
-    if !pname.exists() {
-        return Err(String::from("[Main] File is not found."));
+// `-` stands for stdin
+fn read_payload(fname: &str) -> Result<Vec<u8>> {
+    let mut buf: Vec<u8> = Vec::new();
+    if fname == "-" {
+        io::stdin().lock().read_to_end(&mut buf)?;
+    } else {
+        File::open(fname)?.read_to_end(&mut buf)?;
     }
 
-    let mut buf: Vec<u8> = Vec::new();
+    return Ok(buf);
+}
+
+// `-` stands for stdout
+fn write_payload(fname: &str, buf: &[u8]) -> Result<()> {
+    if fname == "-" {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(buf)?;
+        stdout.flush()?;
+    } else {
+        File::create(fname)?.write_all(buf)?;
+    }
 
-    let mut f = File::open(fname).unwrap();
-    let _ = f.read_to_end(&mut buf);
+    return Ok(());
+}
 
-    return Ok(buf);
+// progress report for `--verbose`, left out of dry runs since nothing is done
+fn note(matches: &Matches, msg: &str) {
+    if matches.is_verbose() && !matches.flag("dry-run") {
+        eprintln!("{}", msg);
+    }
+}
+
+fn parse_mode(matches: &Matches) -> ParseMode {
+    if matches.flag("lenient") {
+        return ParseMode::Lenient;
+    }
+    return ParseMode::Strict;
+}
+
+fn write_options(matches: &Matches) -> Result<WriteOptions> {
+    return WriteOptions::new(
+        matches.value("backup"),
+        matches.flag("dry-run"),
+        matches.is_verbose(),
+    );
+}
+
+// a dry run tells what would change on stdout, a real write only with `--verbose`
+fn report_write(matches: &Matches, report: &WriteReport) {
+    if let Some(failure) = report.sync_failure() {
+        eprintln!(
+            "Warning: {} was written, but may not survive a crash: {}",
+            report.fname(),
+            failure
+        );
+    }
+    if report.is_dry_run() {
+        print!("{}", report);
+    } else {
+        note(matches, report.to_string().trim_end());
+    }
+}
+
+fn usage_error(reason: &str) -> Error {
+    return Error::InvalidUsage(String::from(reason));
+}
+
+fn encode(matches: &Matches) -> Result<()> {
+    let mode = mode(matches)?;
+    let convert = matches.flag("convert");
+    let position: Option<ChunkPosition> = matches.parse_value("position")?;
+    if (convert && matches!(mode, Mode::Chunk))
+        || (position.is_some() && !matches!(mode, Mode::Chunk))
+    {
+        return Err(usage_error(
+            "--convert only applies to the pixel modes and --position to the chunk mode",
+        ));
+    }
+
+    // the pixel modes take no chunk type
+    let args = matches.positional();
+    let positional = if matches!(mode, Mode::Chunk) { 3 } else { 2 };
+    let text = match matches.value("file") {
+        Some(fname) if args.len() == positional => read_payload(fname)?,
+        None if args.len() == positional + 1 => args[positional].as_bytes().to_vec(),
+        _ => {
+            return Err(usage_error(
+                "Give the message either as an argument or with --file",
+            ))
+        }
+    };
+    let mut msg = match encoding(matches)? {
+        Encoding::Utf8 => text,
+        encoding => encoding.decode(&String::from_utf8_lossy(&text))?,
+    };
+    if matches.flag("encrypt") {
+        msg = crypto::encrypt(&msg, read_passphrase()?.as_bytes())?;
+    }
+
+    let (src_fname, dst_fname) = (&args[0], &args[1]);
+    let write_options = write_options(matches)?;
+    let report = match mode {
+        Mode::Chunk => match position {
+            Some(position) => ops::encode_at(
+                src_fname,
+                dst_fname,
+                &args[2],
+                &msg,
+                &position,
+                &write_options,
+            )?,
+            None => ops::encode(src_fname, dst_fname, &args[2], &msg, &write_options)?,
+        },
+        Mode::Lsb(options) => ops::encode_lsb(
+            src_fname,
+            dst_fname,
+            &msg,
+            &options,
+            convert,
+            None,
+            &write_options,
+        )?,
+        Mode::Scatter(options) => {
+            let seed = passphrase_seed()?;
+            ops::encode_lsb(
+                src_fname,
+                dst_fname,
+                &msg,
+                &options,
+                convert,
+                seed,
+                &write_options,
+            )?
+        }
+    };
+    report_write(matches, &report);
+
+    note(
+        matches,
+        &format!("Hid {} bytes in {}", msg.len(), dst_fname),
+    );
+    return Ok(());
 }
 
-fn write_png(fname: &str, buf: &Vec<u8>) -> Result<(), String> {
-    let mut f = File::create(fname).unwrap();
-    let _ = f.write_all(buf);
+fn decode(matches: &Matches) -> Result<()> {
+    let mode = mode(matches)?;
+    let output = matches.value("output");
+    let all = matches.flag("all");
+    let occurrence: Option<usize> = matches.parse_value("occurrence")?;
+    // chunk mode picks among the chunks of the type, and only a single message goes to a file
+    if (all || occurrence.is_some()) && !matches!(mode, Mode::Chunk) {
+        return Err(usage_error(
+            "--all and --occurrence only apply to the chunk mode",
+        ));
+    }
+    if all && (occurrence.is_some() || output.is_some()) {
+        return Err(usage_error(
+            "--all cannot be combined with --occurrence or --output",
+        ));
+    }
+
+    let args = matches.positional();
+    let msgs = match mode {
+        Mode::Chunk if args.len() == 2 && all => ops::decode_all(&args[0], &args[1])?,
+        Mode::Chunk if args.len() == 2 => {
+            let index = occurrence.unwrap_or(0);
+            vec![ops::decode_occurrence(&args[0], &args[1], index)?]
+        }
+        Mode::Lsb(options) if args.len() == 1 => vec![ops::decode_lsb(&args[0], &options, None)?],
+        Mode::Scatter(options) if args.len() == 1 => {
+            vec![ops::decode_lsb(&args[0], &options, passphrase_seed()?)?]
+        }
+        Mode::Chunk => return Err(usage_error("The chunk mode needs a chunk type")),
+        _ => return Err(usage_error("The pixel modes take no chunk type")),
+    };
+
+    let encoding = encoding(matches)?;
+    for (i, mut msg) in msgs.into_iter().enumerate() {
+        if crypto::is_encrypted(&msg) {
+            note(matches, &format!("Decrypting message {}", i));
+            msg = crypto::decrypt(&msg, read_passphrase()?.as_bytes())?;
+        }
+        note(matches, &format!("Message {}: {} bytes", i, msg.len()));
 
+        // raw bytes for utf8, since the file need not hold text
+        if let Some(fname) = output {
+            if encoding == Encoding::Utf8 {
+                return write_payload(fname, &msg);
+            }
+            return write_payload(fname, encoding.encode(&msg)?.as_bytes());
+        }
+        let s = encoding.encode(&msg)?;
+        if all {
+            println!("Decoded Message {}: {}", i, s);
+        } else {
+            println!("Decoded Message: {}", s);
+        }
+    }
     return Ok(());
 }
 
-fn encode(src_fname: &str, dst_fname: &str, chunk_type: &str, msg: &str) -> Result<(), String> {
-    let buf = read_png(src_fname)?;
-    let mut png = Png::from_bytes(&buf)?;
+fn delete(matches: &Matches) -> Result<()> {
+    let all = matches.flag("all");
+    let occurrence: Option<usize> = matches.parse_value("occurrence")?;
+    let selection = match occurrence {
+        Some(_) if all => return Err(usage_error("--all cannot be combined with --occurrence")),
+        Some(index) => ChunkSelection::Occurrence(index),
+        None if all => ChunkSelection::All,
+        None => ChunkSelection::Occurrence(0),
+    };
 
-    let new_chunk = Chunk::from_str(chunk_type, msg)?;
-    png.add_chunk(new_chunk);
+    let (src_fname, chunk_type) = (&matches.positional()[0], &matches.positional()[1]);
+    let dst_fname = matches.value("output").unwrap_or(src_fname);
+    let (count, report) = ops::delete_chunks(
+        src_fname,
+        dst_fname,
+        chunk_type,
+        selection,
+        &write_options(matches)?,
+    )?;
+    report_write(matches, &report);
+    note(
+        matches,
+        &format!("Deleted {} {} chunks from {}", count, chunk_type, src_fname),
+    );
 
-    let new_buf = png.bytes();
-    let _ = write_png(dst_fname, &new_buf)?;
+    return Ok(());
+}
+
+fn capacity(matches: &Matches) -> Result<()> {
+    let options = lsb_options(matches)?;
+    let png = read_png(&matches.positional()[0])?;
+    let bytes = lsb::capacity(&ops::lsb_image(&png, matches.flag("convert"))?, &options)?;
+    println!("Capacity: {} bytes", bytes);
 
     return Ok(());
 }
 
-fn decode(src_fname: &str, chunk_type: &str) -> Result<String, String> {
-    let buf = read_png(src_fname)?;
-    let png = Png::from_bytes(&buf)?;
+fn print(matches: &Matches) -> Result<()> {
+    let format = matches.value("format").unwrap_or("text");
+    let data_encoding: Option<Encoding> = matches.parse_value("data")?;
+    let fnames = matches.positional();
+    match format {
+        "text" | "table" if data_encoding.is_some() => {
+            return Err(usage_error("--data only applies to the JSON formats"))
+        }
+        "text" | "table" => {
+            for fname in fnames {
+                if fnames.len() > 1 {
+                    println!("{}:", fname);
+                }
+                print_text(fname, parse_mode(matches), format == "table")?;
+            }
+            return Ok(());
+        }
+        "json" | "ndjson" => (),
+        _ => return Err(Error::InvalidUsage(format!("Unknown format {:?}", format))),
+    }
+
+    // unreadable files get an error entry, so that the other files are still reported
+    let mut reports: Vec<Json> = Vec::new();
+    let mut failure: Option<Error> = None;
+    for fname in fnames {
+        let mut fields = vec![(String::from("file"), Json::string(fname))];
+        match read_png_with_mode(fname, parse_mode(matches)) {
+            Ok(png) => {
+                if let Json::Object(png_fields) = png.to_json(data_encoding) {
+                    fields.extend(png_fields);
+                }
+            }
+            Err(err) => {
+                fields.push((String::from("error"), Json::String(err.to_string())));
+                failure.get_or_insert(err);
+            }
+        }
 
-    let chunk_res = png.search_chunk(chunk_type);
-    match chunk_res {
-        Some(chunk) => return Ok(String::from(chunk.data_str())),
-        None => return Err(String::from("[Main] Chunk is not found.")),
+        let report = Json::Object(fields);
+        if format == "ndjson" {
+            println!("{}", report.to_compact());
+        } else {
+            reports.push(report);
+        }
+    }
+
+    // a single file is reported as an object, several as an array
+    if format == "json" {
+        let document = match reports.len() {
+            1 => reports.remove(0),
+            _ => Json::Array(reports),
+        };
+        println!("{}", document.to_pretty());
+    }
+    match failure {
+        Some(err) => return Err(err),
+        None => return Ok(()),
     }
 }
 
-fn delete(src_fname: &str, chunk_type: &str) -> Result<(), String> {
-    let buf = read_png(src_fname)?;
-    let mut png = Png::from_bytes(&buf)?;
+fn print_text(src_fname: &str, mode: ParseMode, table: bool) -> Result<()> {
+    let png = read_png_with_mode(src_fname, mode)?;
+    match png.header_info() {
+        Ok(header) => println!("{}", header),
+        Err(Error::ChunkNotFound(_)) => (),
+        Err(err) => println!("ImageHeader: {}", err),
+    }
+    if table {
+        print!("{}", png.summary_table());
+    } else {
+        println!("{}", png);
+    }
 
-    let _ = png.delete_chunk(chunk_type)?;
+    return Ok(());
+}
+
+fn dump(matches: &Matches) -> Result<()> {
+    let args = matches.positional();
+    let occurrence: Option<usize> = matches.parse_value("occurrence")?;
+    let start: usize = matches.parse_value("offset")?.unwrap_or(0);
+    let length: Option<usize> = matches.parse_value("length")?;
+    let full = matches.flag("full");
+    if full && length.is_some() {
+        return Err(usage_error("--full cannot be combined with --length"));
+    }
 
-    let new_buf = png.bytes();
-    let _ = write_png(src_fname, &new_buf)?;
+    // a number selects the chunk by its index in the file, as listed by `print --format table`
+    let png = read_png_with_mode(&args[0], parse_mode(matches))?;
+    let (index, chunk) = match args[1].parse::<usize>() {
+        Ok(_) if occurrence.is_some() => {
+            return Err(usage_error("--occurrence only applies to a chunk type"))
+        }
+        Ok(index) => match png.chunks().get(index) {
+            Some(chunk) => (index, chunk),
+            None => {
+                return Err(Error::InvalidIndex {
+                    index,
+                    len: png.chunks().len(),
+                })
+            }
+        },
+        Err(_) => png.chunk_occurrence(&args[1], occurrence.unwrap_or(0))?,
+    };
+
+    println!(
+        "Chunk {} ({}) at offset {}: {} bytes of data",
+        index,
+        chunk.chunk_type().str(),
+        png.chunk_offsets()[index],
+        chunk.length()
+    );
+    let limit = if full {
+        None
+    } else {
+        Some(length.unwrap_or(DEFAULT_DUMP_LENGTH))
+    };
+    print!("{}", hexdump(chunk.data(), start, limit));
 
     return Ok(());
 }
 
-fn print(src_fname: &str) -> Result<(), String> {
-    let buf = read_png(src_fname)?;
-    let png = Png::from_bytes(&buf)?;
-    print!("{}\n", png);
+fn check(matches: &Matches) -> Result<()> {
+    let src_fname = &matches.positional()[0];
+    let png = read_png_with_mode(src_fname, parse_mode(matches))?;
+    // only a lenient read lets chunks with a bad crc through
+    let mismatches = png.crc_mismatches();
+    let violations = png.validate();
+    if mismatches.is_empty() && violations.is_empty() {
+        println!("OK: {} ({} chunks)", src_fname, png.chunks().len());
+        return Ok(());
+    }
+
+    for mismatch in mismatches.iter() {
+        println!("{}: {}", src_fname, mismatch);
+    }
+    for violation in violations.iter() {
+        println!("{}: {}", src_fname, violation);
+    }
+    return Err(Error::CheckFailed {
+        bad_crcs: mismatches.len(),
+        violations: violations.len(),
+    });
+}
+
+fn repair(matches: &Matches) -> Result<()> {
+    let src_fname = &matches.positional()[0];
+    let dst_fname = matches.value("output").unwrap_or(src_fname);
+    let (repair, report) = ops::repair(
+        src_fname,
+        dst_fname,
+        matches.flag("force"),
+        &write_options(matches)?,
+    )?;
+    if repair.fixed().is_empty() && repair.skipped().is_empty() {
+        println!("OK: {} has no bad CRCs", src_fname);
+    }
+    for mismatch in repair.fixed() {
+        println!("{}: {}", src_fname, mismatch);
+    }
+    for mismatch in repair.skipped() {
+        println!(
+            "{}: {} Skipped, as the chunk is critical.",
+            src_fname, mismatch
+        );
+    }
 
+    if let Some(report) = report {
+        report_write(matches, &report);
+    }
+    note(
+        matches,
+        &format!("Repaired {} CRCs in {}", repair.fixed().len(), dst_fname),
+    );
+    // the ancillary fixes are written, but the file is still damaged
+    if !repair.skipped().is_empty() {
+        return Err(Error::CriticalCrcsSkipped {
+            count: repair.skipped().len(),
+        });
+    }
     return Ok(());
 }
 
-fn execute(args: &Vec<String>) -> Result<(), String> {
-    if args[1] == "encode" && args.len() == 6 {
-        return encode(&args[2], &args[3], &args[4], &args[5]);
-    } else if args[1] == "decode" && args.len() == 4 {
-        let res = decode(&args[2], &args[3]);
-        match res {
-            Ok(s) => {
-                print!("Decoded Message: {}\n", s);
-                return Ok(());
+// writes a PNG of random RGB pixels, e.g. as a cover image for testing
+fn generate(matches: &Matches) -> Result<()> {
+    let args = matches.positional();
+    let width: u32 = args[1].parse().map_err(|_| usage_error("Invalid width"))?;
+    let height: u32 = args[2].parse().map_err(|_| usage_error("Invalid height"))?;
+    // no larger than pngme itself is willing to decode
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .filter(|size| *size <= inflate::DEFAULT_OUTPUT_LIMIT)
+        .ok_or(Error::ImageTooLarge { width, height })?;
+
+    let mut pixels = vec![0u8; size];
+    getrandom::getrandom(&mut pixels)
+        .map_err(|err| Error::Io(io::Error::other(err.to_string())))?;
+    let image = Image::new(width, height, ColorType::Rgb, 8, pixels)?;
+
+    let png = image.to_png(CompressionLevel::Default)?;
+    report_write(
+        matches,
+        &save_png(&args[0], &png, &write_options(matches)?)?,
+    );
+    note(
+        matches,
+        &format!("Wrote a {}x{} image to {}", width, height, args[0]),
+    );
+    return Ok(());
+}
+
+fn text(matches: &Matches) -> Result<()> {
+    let args = matches.positional();
+    let dst_fname = matches.value("output").unwrap_or(&args[0]);
+
+    match matches.command().name {
+        "text list" => {
+            let png = read_png(&args[0])?;
+            for entry in png.text_entries()? {
+                println!(
+                    "{} [{}]: {}",
+                    entry.keyword(),
+                    entry.chunk_type(),
+                    entry.text()
+                );
             }
-            Err(s) => return Err(s),
+            return Ok(());
+        }
+        "text get" => {
+            let png = read_png(&args[0])?;
+            let entries = png.text_entries()?;
+            match entries.iter().find(|entry| entry.keyword() == args[1]) {
+                Some(entry) => {
+                    println!("{}", entry.text());
+                    return Ok(());
+                }
+                None => return Err(Error::TextNotFound(args[1].clone())),
+            }
+        }
+        "text set" => {
+            let compress = matches.flag("compress");
+            let language_tag = matches.value("lang");
+            let translated_keyword = matches.value("translated");
+            let (keyword, value) = (&args[1], &args[2]);
+            let entry = if matches.flag("international")
+                || language_tag.is_some()
+                || translated_keyword.is_some()
+            {
+                TextEntry::International(InternationalTextChunk::new(
+                    keyword,
+                    compress,
+                    language_tag.unwrap_or(""),
+                    translated_keyword.unwrap_or(""),
+                    value,
+                )?)
+            } else if compress {
+                TextEntry::Compressed(CompressedTextChunk::new(keyword, value)?)
+            } else {
+                TextEntry::Text(TextChunk::new(keyword, value)?)
+            };
+
+            let mut png = read_png(&args[0])?;
+            png.set_text(&entry)?;
+            report_write(
+                matches,
+                &save_png(dst_fname, &png, &write_options(matches)?)?,
+            );
+            note(
+                matches,
+                &format!("Set {} in {}", entry.chunk_type(), dst_fname),
+            );
+            return Ok(());
+        }
+        _ => {
+            let mut png = read_png(&args[0])?;
+            let count = png.remove_text(&args[1]);
+            if count == 0 {
+                return Err(Error::TextNotFound(args[1].clone()));
+            }
+            report_write(
+                matches,
+                &save_png(dst_fname, &png, &write_options(matches)?)?,
+            );
+            note(
+                matches,
+                &format!("Removed {} entries from {}", count, dst_fname),
+            );
+            return Ok(());
         }
-    } else if args[1] == "delete" && args.len() == 4 {
-        return delete(&args[2], &args[3]);
-    } else if args[1] == "print" && args.len() == 3 {
-        return print(&args[2]);
-    } else {
-        return Err(String::from(
-            "[Main] Invalid parameters or parameter number.",
-        ));
+    }
+}
+
+fn apng(matches: &Matches) -> Result<()> {
+    let args = matches.positional();
+    let parse_index = |index: &String| {
+        return index
+            .parse::<usize>()
+            .map_err(|_| Error::InvalidUsage(format!("Invalid frame index {:?}", index)));
+    };
+
+    match matches.command().name {
+        "apng list" => {
+            let png = read_png(&args[0])?;
+            print!("{}", png.animation()?);
+            return Ok(());
+        }
+        "apng extract" => {
+            let frame = read_png(&args[0])?.extract_frame(parse_index(&args[1])?)?;
+            report_write(
+                matches,
+                &save_png(&args[2], &frame, &write_options(matches)?)?,
+            );
+            return Ok(());
+        }
+        "apng drop" => {
+            let mut indices = args[1..]
+                .iter()
+                .map(parse_index)
+                .collect::<Result<Vec<usize>>>()?;
+            indices.sort_unstable();
+            indices.dedup();
+            let mut png = read_png(&args[0])?;
+            let dropped = png.drop_frames(&indices)?;
+
+            let dst_fname = matches.value("output").unwrap_or(&args[0]);
+            report_write(
+                matches,
+                &save_png(dst_fname, &png, &write_options(matches)?)?,
+            );
+            note(matches, &format!("Dropped {} frames", dropped));
+            return Ok(());
+        }
+        _ => {
+            // every frame is drawn at the canvas origin for `--delay` milliseconds
+            let delay: u16 = matches.parse_value("delay")?.unwrap_or(100);
+            let num_plays: u32 = matches.parse_value("plays")?.unwrap_or(0);
+
+            let mut frames: Vec<(Image, FrameControl)> = Vec::new();
+            for fname in args[1..].iter() {
+                let image = Image::from_png(&read_png(fname)?, inflate::DEFAULT_OUTPUT_LIMIT)?;
+                let mut frame_control = FrameControl::new(image.width(), image.height(), 0, 0)?;
+                frame_control.set_delay(delay, 1000);
+                frames.push((image, frame_control));
+            }
+            let png = Animation::build(&frames, num_plays, CompressionLevel::Default)?;
+            report_write(
+                matches,
+                &save_png(&args[0], &png, &write_options(matches)?)?,
+            );
+            note(
+                matches,
+                &format!("Wrote {} frames to {}", frames.len(), args[0]),
+            );
+            return Ok(());
+        }
+    }
+}
+
+// where `encode` hides the message and `decode` looks for it
+enum Mode {
+    Chunk,
+    Lsb(LsbOptions),
+    // LSB samples visited in an order derived from the passphrase
+    Scatter(LsbOptions),
+}
+
+// `--mode` with the `--channels` and `--bits` options of the pixel modes
+fn mode(matches: &Matches) -> Result<Mode> {
+    match matches.value("mode") {
+        None | Some("chunk") => return Ok(Mode::Chunk),
+        Some("lsb") => return Ok(Mode::Lsb(lsb_options(matches)?)),
+        Some("scatter") => return Ok(Mode::Scatter(lsb_options(matches)?)),
+        Some(mode) => return Err(Error::InvalidUsage(format!("Unknown mode {:?}", mode))),
+    }
+}
+
+fn lsb_options(matches: &Matches) -> Result<LsbOptions> {
+    let bits = matches.parse_value("bits")?.unwrap_or(1);
+    return LsbOptions::new(matches.value("channels"), bits);
+}
+
+fn encoding(matches: &Matches) -> Result<Encoding> {
+    return Ok(matches.parse_value("encoding")?.unwrap_or(Encoding::Utf8));
+}
+
+fn passphrase_seed() -> Result<Option<[u8; 32]>> {
+    return Ok(Some(crypto::derive_seed(read_passphrase()?.as_bytes())?));
+}
+
+fn read_passphrase() -> Result<String> {
+    match env::var("PNGME_PASSPHRASE") {
+        Ok(passphrase) if !passphrase.is_empty() => return Ok(passphrase),
+        _ => return Err(Error::PassphraseRequired),
+    }
+}
+
+fn execute(matches: &Matches) -> Result<()> {
+    match matches.command().name {
+        "encode" => return encode(matches),
+        "decode" => return decode(matches),
+        "delete" => return delete(matches),
+        "print" => return print(matches),
+        "dump" => return dump(matches),
+        "check" => return check(matches),
+        "repair" => return repair(matches),
+        "capacity" => return capacity(matches),
+        "generate" => return generate(matches),
+        name if name.starts_with("text ") => return text(matches),
+        _ => return apng(matches),
+    }
+}
+
+fn exit_code(kind: ErrorKind) -> i32 {
+    match kind {
+        ErrorKind::Other => return 1,
+        ErrorKind::Usage => return 2,
+        ErrorKind::NotFound => return 3,
+        ErrorKind::Corrupt => return 4,
+        ErrorKind::Io => return 5,
+        // kinds added later count as generic failures until they get their own status
+        _ => return 1,
     }
 }
 
 fn main() {
     let args = read_args();
-    if args.len() < 3 {
-        print!("[Main] Invalid number of arguments.\n");
-        return;
-    }
+    let res = cli::parse(&args).and_then(|invocation| match invocation {
+        Invocation::Help(help) => {
+            println!("{}", help);
+            return Ok(());
+        }
+        Invocation::Run(matches) => return execute(&matches),
+    });
 
-    let res = execute(&args);
-    match res {
-        Ok(()) => (),
-        Err(s) => print!("{}\n", s),
+    if let Err(err) = res {
+        eprintln!("{}", err);
+        if matches!(err, Error::InvalidUsage(_) | Error::InvalidArguments) {
+            match Command::find(&args) {
+                Some(command) => eprintln!(
+                    "{}\nRun `pngme {} --help` for more information.",
+                    command.usage(),
+                    command.name
+                ),
+                None => eprintln!("Run `pngme --help` for the list of commands."),
+            }
+        }
+        if matches!(err, Error::InvalidCrc { .. }) {
+            eprintln!(
+                "Run `pngme repair` to fix the CRC, or `pngme print --lenient` to inspect the file."
+            );
+        }
+        if matches!(err, Error::CriticalCrcsSkipped { .. }) {
+            eprintln!("Pass --force to repair them anyway.");
+        }
+        process::exit(exit_code(err.kind()));
     }
 }
@@ -0,0 +1,716 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{self, Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+
+use crate::chunk::{Chunk, ParseMode};
+use crate::chunk_type::ChunkType;
+use crate::deflate::CompressionLevel;
+use crate::error::{Error, Result};
+use crate::image::Image;
+use crate::inflate;
+use crate::lsb::{self, LsbOptions};
+use crate::png::{ChunkPosition, ChunkSelection, Png};
+use crate::reader::PngReader;
+use crate::repair::CrcRepair;
+use crate::writer::PngWriter;
+
+// how the operations below write their output file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    // the replaced file is kept as `fname` + suffix
+    m_backup_suffix: Option<String>,
+    m_dry_run: bool,
+    // whether a real write works out its chunk changes too, which dry runs always do
+    m_report_changes: bool,
+}
+
+impl WriteOptions {
+    pub fn new(backup_suffix: Option<&str>, dry_run: bool, report_changes: bool) -> Result<Self> {
+        if let Some(suffix) = backup_suffix {
+            if suffix.is_empty() || suffix.chars().any(path::is_separator) {
+                return Err(Error::InvalidBackupSuffix(String::from(suffix)));
+            }
+        }
+
+        return Ok(Self {
+            m_backup_suffix: backup_suffix.map(String::from),
+            m_dry_run: dry_run,
+            m_report_changes: report_changes,
+        });
+    }
+
+    pub fn backup_suffix(&self) -> Option<&str> {
+        return self.m_backup_suffix.as_deref();
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        return self.m_dry_run;
+    }
+
+    pub fn reports_changes(&self) -> bool {
+        return self.m_dry_run || self.m_report_changes;
+    }
+}
+
+// a chunk that a write adds or removes, with its index in the new or the old file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkChange {
+    Added {
+        index: usize,
+        chunk_type: String,
+        length: u32,
+    },
+    Removed {
+        index: usize,
+        chunk_type: String,
+        length: u32,
+    },
+}
+
+impl fmt::Display for ChunkChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (action, index, chunk_type, length) = match self {
+            Self::Added {
+                index,
+                chunk_type,
+                length,
+            } => ("added", index, chunk_type, length),
+            Self::Removed {
+                index,
+                chunk_type,
+                length,
+            } => ("removed", index, chunk_type, length),
+        };
+        write!(
+            f,
+            "{} {} ({} bytes) at index {}",
+            action, chunk_type, length, index
+        )
+    }
+}
+
+// what a write did, or would do for a dry run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteReport {
+    m_fname: String,
+    m_dry_run: bool,
+    m_backup_fname: Option<String>,
+    // `None` if the file did not exist
+    m_old_length: Option<u64>,
+    m_new_length: u64,
+    // `None` unless asked for, see `WriteOptions::reports_changes`
+    m_changes: Option<Vec<ChunkChange>>,
+    // why the directory could not be synced after the file was replaced
+    m_sync_failure: Option<String>,
+}
+
+impl WriteReport {
+    pub fn fname(&self) -> &str {
+        return &self.m_fname;
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        return self.m_dry_run;
+    }
+
+    pub fn backup_fname(&self) -> Option<&str> {
+        return self.m_backup_fname.as_deref();
+    }
+
+    pub fn old_length(&self) -> Option<u64> {
+        return self.m_old_length;
+    }
+
+    pub fn new_length(&self) -> u64 {
+        return self.m_new_length;
+    }
+
+    pub fn changes(&self) -> Option<&[ChunkChange]> {
+        return self.m_changes.as_deref();
+    }
+
+    // the file was replaced, but the rename may not survive a crash
+    pub fn sync_failure(&self) -> Option<&str> {
+        return self.m_sync_failure.as_deref();
+    }
+}
+
+impl fmt::Display for WriteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match (self.is_dry_run(), self.old_length()) {
+            (true, Some(_)) => "Would replace",
+            (true, None) => "Would create",
+            (false, Some(_)) => "Replaced",
+            (false, None) => "Created",
+        };
+        match self.old_length() {
+            Some(old_length) => writeln!(
+                f,
+                "{} {} ({} -> {} bytes)",
+                action,
+                self.fname(),
+                old_length,
+                self.new_length()
+            )?,
+            None => writeln!(
+                f,
+                "{} {} ({} bytes)",
+                action,
+                self.fname(),
+                self.new_length()
+            )?,
+        }
+        if let Some(backup_fname) = self.backup_fname() {
+            writeln!(f, "  original kept as {}", backup_fname)?;
+        }
+        match self.changes() {
+            Some([]) => writeln!(f, "  no chunk changes")?,
+            Some(changes) => {
+                for change in changes {
+                    writeln!(f, "  {}", change)?;
+                }
+            }
+            None => (),
+        }
+        return Ok(());
+    }
+}
+
+// what tells chunks apart when comparing the old and new file
+#[derive(PartialEq, Eq)]
+struct ChunkSummary {
+    m_chunk_type: String,
+    m_length: u32,
+    m_crc: u32,
+}
+
+fn chunk_summaries<R: Read>(reader: PngReader<R>) -> Result<Vec<ChunkSummary>> {
+    let mut summaries: Vec<ChunkSummary> = Vec::new();
+    for chunk in reader {
+        let chunk = chunk?;
+        summaries.push(ChunkSummary {
+            m_chunk_type: String::from(chunk.chunk_type().str()),
+            m_length: chunk.length(),
+            m_crc: chunk.crc(),
+        });
+    }
+
+    return Ok(summaries);
+}
+
+// chunks outside the common prefix and suffix of the two lists, in linear time and memory; exact
+// for an edit of a single run of chunks, while scattered edits also list the unchanged chunks in
+// between as removed and added again
+fn chunk_changes(old: &[ChunkSummary], new: &[ChunkSummary]) -> Vec<ChunkChange> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let removed = (prefix..(old.len() - suffix)).map(|i| ChunkChange::Removed {
+        index: i,
+        chunk_type: old[i].m_chunk_type.clone(),
+        length: old[i].m_length,
+    });
+    let added = (prefix..(new.len() - suffix)).map(|j| ChunkChange::Added {
+        index: j,
+        chunk_type: new[j].m_chunk_type.clone(),
+        length: new[j].m_length,
+    });
+    return removed.chain(added).collect();
+}
+
+// summaries of the chunks in `fname`, empty if it is missing or not a valid PNG, since it is
+// then replaced as a whole
+fn old_chunk_summaries(fname: &str) -> Vec<ChunkSummary> {
+    match open_png_with_mode(fname, ParseMode::Lenient) {
+        Ok(reader) => return chunk_summaries(reader).unwrap_or_default(),
+        Err(_) => return Vec::new(),
+    }
+}
+
+fn open_png(fname: &str) -> Result<PngReader<BufReader<File>>> {
+    return open_png_with_mode(fname, ParseMode::Strict);
+}
+
+fn open_png_with_mode(fname: &str, mode: ParseMode) -> Result<PngReader<BufReader<File>>> {
+    let f = File::open(fname)?;
+    return PngReader::with_mode(BufReader::new(f), mode);
+}
+
+fn render_png<F>(out: &mut dyn Write, write: F) -> Result<()>
+where
+    F: FnOnce(&mut PngWriter<&mut dyn Write>) -> Result<()>,
+{
+    let mut writer = PngWriter::new(out)?;
+    write(&mut writer)?;
+    writer.finish()?;
+
+    return Ok(());
+}
+
+// a fresh file next to `fname`, so that renaming it over `fname` stays on the same file system
+fn temp_fname(fname: &str) -> PathBuf {
+    let path = Path::new(fname);
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => String::from("pngme"),
+    };
+    return path.with_file_name(format!(".{}.{}.tmp", name, process::id()));
+}
+
+// writes and syncs `tmp_fname`, with the permissions of `fname` if it exists
+fn create_temp<F>(tmp_fname: &Path, fname: &str, write: F) -> Result<()>
+where
+    F: FnOnce(&mut PngWriter<&mut dyn Write>) -> Result<()>,
+{
+    let f = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(tmp_fname)?;
+    if let Ok(metadata) = fs::metadata(fname) {
+        f.set_permissions(metadata.permissions())?;
+    }
+
+    let mut out = BufWriter::new(f);
+    render_png(&mut out, write)?;
+    let f = out.into_inner().map_err(|err| err.into_error())?;
+    f.sync_all()?;
+
+    return Ok(());
+}
+
+// makes a rename in the directory of `fname` durable
+#[cfg(unix)]
+fn sync_dir(fname: &str) -> Result<()> {
+    let dir = match Path::new(fname).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()?;
+
+    return Ok(());
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_fname: &str) -> Result<()> {
+    return Ok(());
+}
+
+// copies `fname` to a new, synced `backup_fname`, made durable before `fname` is replaced
+fn create_backup(fname: &str, backup_fname: &str) -> Result<()> {
+    let mut backup = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(backup_fname)
+    {
+        Ok(backup) => backup,
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(Error::BackupExists(String::from(backup_fname)));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let mut copy = || -> Result<()> {
+        let original = File::open(fname)?;
+        backup.set_permissions(original.metadata()?.permissions())?;
+        io::copy(&mut BufReader::new(original), &mut backup)?;
+        backup.sync_all()?;
+        return sync_dir(backup_fname);
+    };
+
+    let result = copy();
+    if result.is_err() {
+        let _ = fs::remove_file(backup_fname);
+    }
+    return result;
+}
+
+// writes `fname` atomically: the data goes to a synced temporary file, which is renamed over
+// `fname`, so that `fname` may also be the file being read and a failure at any point leaves it
+// untouched; a dry run only renders the data in memory
+fn write_png<F>(fname: &str, options: &WriteOptions, write: F) -> Result<WriteReport>
+where
+    F: FnOnce(&mut PngWriter<&mut dyn Write>) -> Result<()>,
+{
+    let old_length = fs::metadata(fname).ok().map(|metadata| metadata.len());
+    let backup_fname = match options.backup_suffix() {
+        Some(suffix) if old_length.is_some() => Some(format!("{}{}", fname, suffix)),
+        _ => None,
+    };
+    // an earlier backup is never overwritten, not even by the same command run twice
+    if let Some(backup_fname) = &backup_fname {
+        if fs::symlink_metadata(backup_fname).is_ok() {
+            return Err(Error::BackupExists(backup_fname.clone()));
+        }
+    }
+    let old_chunks = if options.reports_changes() {
+        old_chunk_summaries(fname)
+    } else {
+        Vec::new()
+    };
+
+    if options.is_dry_run() {
+        let mut bytes: Vec<u8> = Vec::new();
+        render_png(&mut bytes, write)?;
+        let new_chunks = chunk_summaries(PngReader::with_mode(&bytes[..], ParseMode::Lenient)?)?;
+        return Ok(WriteReport {
+            m_fname: String::from(fname),
+            m_dry_run: true,
+            m_backup_fname: backup_fname,
+            m_old_length: old_length,
+            m_new_length: bytes.len() as u64,
+            m_changes: Some(chunk_changes(&old_chunks, &new_chunks)),
+            m_sync_failure: None,
+        });
+    }
+
+    let tmp_fname = temp_fname(fname);
+    let mut created_backup = false;
+    let prepare = || -> Result<WriteReport> {
+        create_temp(&tmp_fname, fname, write)?;
+        // the report is complete before the rename, so nothing can fail after the write is done
+        let changes = if options.reports_changes() {
+            let f = File::open(&tmp_fname)?;
+            let reader = PngReader::with_mode(BufReader::new(f), ParseMode::Lenient)?;
+            Some(chunk_changes(&old_chunks, &chunk_summaries(reader)?))
+        } else {
+            None
+        };
+        let new_length = fs::metadata(&tmp_fname)?.len();
+
+        if let Some(backup_fname) = &backup_fname {
+            create_backup(fname, backup_fname)?;
+            created_backup = true;
+        }
+        let report = WriteReport {
+            m_fname: String::from(fname),
+            m_dry_run: false,
+            m_backup_fname: backup_fname.clone(),
+            m_old_length: old_length,
+            m_new_length: new_length,
+            m_changes: changes,
+            m_sync_failure: None,
+        };
+        fs::rename(&tmp_fname, fname)?;
+        return Ok(report);
+    };
+
+    let mut report = match prepare() {
+        Ok(report) => report,
+        Err(err) => {
+            let _ = fs::remove_file(&tmp_fname);
+            if let (true, Some(backup_fname)) = (created_backup, &backup_fname) {
+                let _ = fs::remove_file(backup_fname);
+            }
+            return Err(err);
+        }
+    };
+    // `fname` is replaced from here on, so the backup stays and a failed sync is only reported
+    if let Err(err) = sync_dir(fname) {
+        report.m_sync_failure = Some(err.to_string());
+    }
+    return Ok(report);
+}
+
+pub fn read_png(fname: &str) -> Result<Png> {
+    return read_png_with_mode(fname, ParseMode::Strict);
+}
+
+pub fn read_png_with_mode(fname: &str, mode: ParseMode) -> Result<Png> {
+    let reader = open_png_with_mode(fname, mode)?;
+    return Ok(Png::from_chunks(reader.collect::<Result<Vec<Chunk>>>()?));
+}
+
+pub fn save_png(fname: &str, png: &Png, options: &WriteOptions) -> Result<WriteReport> {
+    return write_png(fname, options, |writer| {
+        for chunk in png.chunks() {
+            writer.write_chunk(chunk)?;
+        }
+        return Ok(());
+    });
+}
+
+// copies `src_fname` to `dst_fname` with `msg` in a new `chunk_type` chunk right before IEND
+pub fn encode(
+    src_fname: &str,
+    dst_fname: &str,
+    chunk_type: &str,
+    msg: &[u8],
+    options: &WriteOptions,
+) -> Result<WriteReport> {
+    return encode_at(
+        src_fname,
+        dst_fname,
+        chunk_type,
+        msg,
+        &ChunkPosition::BeforeIend,
+        options,
+    );
+}
+
+// like `encode`, with the message chunk at `position` instead of right before IEND; the position
+// is resolved while streaming, so a missing anchor chunk is only found once the file is read
+pub fn encode_at(
+    src_fname: &str,
+    dst_fname: &str,
+    chunk_type: &str,
+    msg: &[u8],
+    position: &ChunkPosition,
+    options: &WriteOptions,
+) -> Result<WriteReport> {
+    let reader = open_png(src_fname)?;
+    let new_chunk = Chunk::new(ChunkType::from_str(chunk_type)?, msg.to_vec())?;
+
+    return write_png(dst_fname, options, |writer| {
+        let mut inserted = false;
+        let mut count = 0;
+        for chunk in reader {
+            let chunk = chunk?;
+            let anchor = chunk.chunk_type().str();
+            let before = match position {
+                ChunkPosition::BeforeIend => anchor == "IEND",
+                ChunkPosition::BeforeIdat => anchor == "IDAT",
+                ChunkPosition::Before(chunk_type) => anchor == chunk_type,
+                ChunkPosition::Index(index) => count == *index,
+                _ => false,
+            };
+            let after = match position {
+                ChunkPosition::AfterIhdr => anchor == "IHDR",
+                ChunkPosition::After(chunk_type) => anchor == chunk_type,
+                _ => false,
+            };
+
+            if !inserted && before {
+                writer.write_chunk(&new_chunk)?;
+                inserted = true;
+            }
+            writer.write_chunk(&chunk)?;
+            if !inserted && after {
+                writer.write_chunk(&new_chunk)?;
+                inserted = true;
+            }
+            count += 1;
+        }
+
+        if !inserted {
+            match position {
+                ChunkPosition::End | ChunkPosition::BeforeIend => (),
+                ChunkPosition::Index(index) if *index == count => (),
+                ChunkPosition::Index(index) => {
+                    return Err(Error::InvalidIndex {
+                        index: *index,
+                        len: count,
+                    })
+                }
+                ChunkPosition::AfterIhdr => return Err(Error::ChunkNotFound(String::from("IHDR"))),
+                ChunkPosition::BeforeIdat => {
+                    return Err(Error::ChunkNotFound(String::from("IDAT")))
+                }
+                ChunkPosition::Before(chunk_type) | ChunkPosition::After(chunk_type) => {
+                    return Err(Error::ChunkNotFound(chunk_type.clone()))
+                }
+            }
+            writer.write_chunk(&new_chunk)?;
+        }
+        return Ok(());
+    });
+}
+
+// data of the first `chunk_type` chunk
+pub fn decode(src_fname: &str, chunk_type: &str) -> Result<Vec<u8>> {
+    let reader = open_png(src_fname)?;
+
+    for chunk in reader {
+        let chunk = chunk?;
+        if chunk.chunk_type().str() == chunk_type {
+            return Ok(chunk.data().to_vec());
+        }
+    }
+
+    return Err(Error::ChunkNotFound(String::from(chunk_type)));
+}
+
+// data of every `chunk_type` chunk, in file order
+pub fn decode_all(src_fname: &str, chunk_type: &str) -> Result<Vec<Vec<u8>>> {
+    let reader = open_png(src_fname)?;
+    let mut msgs: Vec<Vec<u8>> = Vec::new();
+    for chunk in reader {
+        let chunk = chunk?;
+        if chunk.chunk_type().str() == chunk_type {
+            msgs.push(chunk.data().to_vec());
+        }
+    }
+
+    if msgs.is_empty() {
+        return Err(Error::ChunkNotFound(String::from(chunk_type)));
+    }
+    return Ok(msgs);
+}
+
+// data of the `index`-th `chunk_type` chunk, counting from 0
+pub fn decode_occurrence(src_fname: &str, chunk_type: &str, index: usize) -> Result<Vec<u8>> {
+    let reader = open_png(src_fname)?;
+    let mut count = 0;
+    for chunk in reader {
+        let chunk = chunk?;
+        if chunk.chunk_type().str() == chunk_type {
+            if count == index {
+                return Ok(chunk.data().to_vec());
+            }
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return Err(Error::ChunkNotFound(String::from(chunk_type)));
+    }
+    return Err(Error::InvalidOccurrence {
+        chunk_type: String::from(chunk_type),
+        index,
+        count,
+    });
+}
+
+// removes the first `chunk_type` chunk from `src_fname` in place
+pub fn delete(src_fname: &str, chunk_type: &str, options: &WriteOptions) -> Result<WriteReport> {
+    let (_, report) = delete_chunks(
+        src_fname,
+        src_fname,
+        chunk_type,
+        ChunkSelection::Occurrence(0),
+        options,
+    )?;
+    return Ok(report);
+}
+
+// copies `src_fname` to `dst_fname` without the selected `chunk_type` chunks, returning how many
+// there were; `dst_fname` may be `src_fname`
+pub fn delete_chunks(
+    src_fname: &str,
+    dst_fname: &str,
+    chunk_type: &str,
+    selection: ChunkSelection,
+    options: &WriteOptions,
+) -> Result<(usize, WriteReport)> {
+    // validate the whole file and count the chunks of the type before rewriting anything
+    let reader = open_png(src_fname)?;
+    let (mut count, mut matching) = (0, 0);
+    for chunk in reader {
+        let chunk = chunk?;
+        if chunk.chunk_type().str() == chunk_type {
+            count += 1;
+            if let ChunkSelection::Matching(predicate) = selection {
+                if predicate(&chunk) {
+                    matching += 1;
+                }
+            }
+        }
+    }
+    // a predicate that matches nothing leaves the file alone
+    let nothing_matches = matches!(selection, ChunkSelection::Matching(_)) && matching == 0;
+    if count == 0 || nothing_matches {
+        return Err(Error::ChunkNotFound(String::from(chunk_type)));
+    }
+    if let ChunkSelection::Occurrence(index) = selection {
+        if index >= count {
+            return Err(Error::InvalidOccurrence {
+                chunk_type: String::from(chunk_type),
+                index,
+                count,
+            });
+        }
+    }
+
+    let reader = open_png(src_fname)?;
+    let mut deleted = 0;
+    let report = write_png(dst_fname, options, |writer| {
+        let mut occurrence = 0;
+        for chunk in reader {
+            let chunk = chunk?;
+            if chunk.chunk_type().str() == chunk_type {
+                let selected = match selection {
+                    ChunkSelection::All => true,
+                    ChunkSelection::Occurrence(index) => occurrence == index,
+                    ChunkSelection::Matching(predicate) => predicate(&chunk),
+                };
+                occurrence += 1;
+                if selected {
+                    deleted += 1;
+                    continue;
+                }
+            }
+            writer.write_chunk(&chunk)?;
+        }
+        return Ok(());
+    })?;
+
+    return Ok((deleted, report));
+}
+
+// copies `src_fname` to `dst_fname` with its bad crcs recomputed, returning which were fixed and
+// which skipped; a file repaired in place is left alone if none were fixed
+pub fn repair(
+    src_fname: &str,
+    dst_fname: &str,
+    force: bool,
+    options: &WriteOptions,
+) -> Result<(CrcRepair, Option<WriteReport>)> {
+    let mut png = read_png_with_mode(src_fname, ParseMode::Lenient)?;
+    let repair = png.repair_crcs(force);
+    if repair.fixed().is_empty() && src_fname == dst_fname {
+        return Ok((repair, None));
+    }
+
+    let report = save_png(dst_fname, &png, options)?;
+    return Ok((repair, Some(report)));
+}
+
+// decoded pixels of `png`, converted to 8-bit truecolor first if asked and needed
+pub fn lsb_image(png: &Png, convert: bool) -> Result<Image> {
+    let image = Image::from_png(png, inflate::DEFAULT_OUTPUT_LIMIT)?;
+    if convert && !lsb::is_supported(&image) {
+        return image.to_truecolor();
+    }
+
+    return Ok(image);
+}
+
+// hides `msg` in the pixel samples, in an order derived from `seed` if there is one
+pub fn encode_lsb(
+    src_fname: &str,
+    dst_fname: &str,
+    msg: &[u8],
+    options: &LsbOptions,
+    convert: bool,
+    seed: Option<[u8; 32]>,
+    write_options: &WriteOptions,
+) -> Result<WriteReport> {
+    let mut png = read_png(src_fname)?;
+    let mut image = lsb_image(&png, convert)?;
+    match seed {
+        Some(seed) => lsb::embed_scattered(&mut image, msg, options, &seed)?,
+        None => lsb::embed(&mut image, msg, options)?,
+    }
+    png.replace_image(&image, CompressionLevel::Default)?;
+
+    return save_png(dst_fname, &png, write_options);
+}
+
+pub fn decode_lsb(
+    src_fname: &str,
+    options: &LsbOptions,
+    seed: Option<[u8; 32]>,
+) -> Result<Vec<u8>> {
+    let png = read_png(src_fname)?;
+    let image = lsb_image(&png, false)?;
+    match seed {
+        Some(seed) => return lsb::extract_scattered(&image, options, &seed),
+        None => return lsb::extract(&image, options),
+    }
+}
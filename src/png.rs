@@ -1,42 +1,83 @@
 use std::fmt;
-use std::str::from_utf8;
+use std::str::FromStr;
 
-use crate::chunk::Chunk;
+use crate::apng::{self, Animation};
+use crate::chunk::{Chunk, ParseMode};
+use crate::chunk_ref::ChunkRefs;
+use crate::deflate::CompressionLevel;
+use crate::encoding::Encoding;
+use crate::error::{Error, Result};
+use crate::image::Image;
+use crate::image_header::ImageHeader;
+use crate::inflate;
+use crate::json::Json;
+use crate::repair::{self, CrcMismatch, CrcRepair};
+use crate::text_chunk::{self, TextEntry};
+use crate::validation::{self, Violation};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Png {
-    m_chunks: Vec<Chunk>,
+pub enum ChunkPosition {
+    End,
+    BeforeIend,
+    AfterIhdr,
+    BeforeIdat,
+    Index(usize),
+    Before(String),
+    After(String),
 }
 
-impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+// `end`, `before-iend`, `after-ihdr`, `before-idat`, `index:<n>`, `before:<type>` or `after:<type>`
+impl FromStr for ChunkPosition {
+    type Err = Error;
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        let len = bytes.len() as usize;
-        if len < 8 {
-            return Err(String::from("[Png] Invalid byte length."));
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "end" => return Ok(Self::End),
+            None if s == "before-iend" => return Ok(Self::BeforeIend),
+            None if s == "after-ihdr" => return Ok(Self::AfterIhdr),
+            None if s == "before-idat" => return Ok(Self::BeforeIdat),
+            Some(("index", index)) => {
+                if let Ok(index) = index.parse() {
+                    return Ok(Self::Index(index));
+                }
+            }
+            Some(("before", chunk_type)) => return Ok(Self::Before(String::from(chunk_type))),
+            Some(("after", chunk_type)) => return Ok(Self::After(String::from(chunk_type))),
+            _ => (),
         }
 
-        let header_bytes: [u8; 8] = bytes[0..8].try_into().unwrap();
-        if header_bytes != Self::STANDARD_HEADER {
-            return Err(String::from("[Png] Invalid header values."));
-        }
+        return Err(Error::InvalidUsage(format!(
+            "Unknown chunk position {:?}",
+            s
+        )));
+    }
+}
 
-        let mut m_chunks: Vec<Chunk> = Vec::new();
-        let mut i: usize = 8;
+// which chunks of a given type `delete_chunks` removes
+#[derive(Clone, Copy)]
+pub enum ChunkSelection<'a> {
+    All,
+    // n-th chunk of the type, counting from 0
+    Occurrence(usize),
+    Matching(&'a dyn Fn(&Chunk) -> bool),
+}
 
-        while i < len {
-            let data_len_bytes: [u8; 4] = bytes[i..i + 4].try_into().unwrap();
-            let data_len = u32::from_be_bytes(data_len_bytes);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Png {
+    m_chunks: Vec<Chunk>,
+}
 
-            // length(4 bytes) + chunk_type(4 bytes) + chunk_data(length bytes) + crc(4 bytes)
-            let total_len = data_len + 12;
+impl Png {
+    pub(crate) const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
-            let chunk_bytes = &bytes[i..i + (total_len as usize)];
-            let chunk = Chunk::from_bytes(chunk_bytes)?;
-            m_chunks.push(chunk);
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        return Self::from_bytes_with_mode(bytes, ParseMode::Strict);
+    }
 
-            i += total_len as usize;
+    pub fn from_bytes_with_mode(bytes: &[u8], mode: ParseMode) -> Result<Self> {
+        let mut m_chunks: Vec<Chunk> = Vec::new();
+        for chunk_ref in ChunkRefs::with_mode(bytes, mode)? {
+            m_chunks.push(chunk_ref?.to_chunk());
         }
 
         return Ok(Self { m_chunks });
@@ -58,36 +99,370 @@ impl Png {
         self.m_chunks.push(chunk);
     }
 
-    pub fn delete_chunk(&mut self, chunk_type: &str) -> Result<(), String> {
-        if let Some(i) = self
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) -> Result<()> {
+        let len = self.m_chunks.len();
+        if index > len {
+            return Err(Error::InvalidIndex { index, len });
+        }
+
+        self.m_chunks.insert(index, chunk);
+        return Ok(());
+    }
+
+    pub fn insert_before(&mut self, chunk_type: &str, chunk: Chunk) -> Result<()> {
+        match self.position(chunk_type) {
+            Some(i) => return self.insert_chunk(i, chunk),
+            None => return Err(Error::ChunkNotFound(String::from(chunk_type))),
+        }
+    }
+
+    pub fn insert_after(&mut self, chunk_type: &str, chunk: Chunk) -> Result<()> {
+        match self.position(chunk_type) {
+            Some(i) => return self.insert_chunk(i + 1, chunk),
+            None => return Err(Error::ChunkNotFound(String::from(chunk_type))),
+        }
+    }
+
+    // falls back to appending when the file has no IEND chunk
+    pub fn insert_before_iend(&mut self, chunk: Chunk) {
+        let i = self.position("IEND").unwrap_or(self.m_chunks.len());
+        self.m_chunks.insert(i, chunk);
+    }
+
+    pub fn insert_after_ihdr(&mut self, chunk: Chunk) -> Result<()> {
+        return self.insert_after("IHDR", chunk);
+    }
+
+    pub fn insert_before_idat(&mut self, chunk: Chunk) -> Result<()> {
+        return self.insert_before("IDAT", chunk);
+    }
+
+    pub fn insert_chunk_at(&mut self, position: &ChunkPosition, chunk: Chunk) -> Result<()> {
+        match position {
+            ChunkPosition::End => self.add_chunk(chunk),
+            ChunkPosition::BeforeIend => self.insert_before_iend(chunk),
+            ChunkPosition::AfterIhdr => return self.insert_after_ihdr(chunk),
+            ChunkPosition::BeforeIdat => return self.insert_before_idat(chunk),
+            ChunkPosition::Index(index) => return self.insert_chunk(*index, chunk),
+            ChunkPosition::Before(chunk_type) => return self.insert_before(chunk_type, chunk),
+            ChunkPosition::After(chunk_type) => return self.insert_after(chunk_type, chunk),
+        }
+
+        return Ok(());
+    }
+
+    fn position(&self, chunk_type: &str) -> Option<usize> {
+        return self
             .chunks()
             .iter()
-            .position(|chunk| chunk.chunk_type().str() == chunk_type)
-        {
-            let _ = self.m_chunks.remove(i);
-            return Ok(());
+            .position(|chunk| chunk.chunk_type().str() == chunk_type);
+    }
+
+    pub fn delete_chunk(&mut self, chunk_type: &str) -> Result<()> {
+        let _ = self.delete_chunks(chunk_type, ChunkSelection::Occurrence(0))?;
+        return Ok(());
+    }
+
+    // removes the selected chunks of `chunk_type`, returning them in file order
+    pub fn delete_chunks(
+        &mut self,
+        chunk_type: &str,
+        selection: ChunkSelection,
+    ) -> Result<Vec<Chunk>> {
+        let found: Vec<usize> = self
+            .search_chunks(chunk_type)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect();
+        if found.is_empty() {
+            return Err(Error::ChunkNotFound(String::from(chunk_type)));
         }
 
-        return Err(String::from("[Png] Cannot remove chunk type."));
+        let selected: Vec<usize> = match selection {
+            ChunkSelection::All => found,
+            ChunkSelection::Occurrence(index) => match found.get(index) {
+                Some(i) => vec![*i],
+                None => {
+                    return Err(Error::InvalidOccurrence {
+                        chunk_type: String::from(chunk_type),
+                        index,
+                        count: found.len(),
+                    })
+                }
+            },
+            ChunkSelection::Matching(predicate) => found
+                .into_iter()
+                .filter(|i| predicate(&self.m_chunks[*i]))
+                .collect(),
+        };
+        if selected.is_empty() {
+            return Err(Error::ChunkNotFound(String::from(chunk_type)));
+        }
+
+        // back to front, so that the remaining indices stay valid
+        let mut deleted: Vec<Chunk> = selected
+            .into_iter()
+            .rev()
+            .map(|i| self.m_chunks.remove(i))
+            .collect();
+        deleted.reverse();
+        return Ok(deleted);
     }
 
     pub fn search_chunk(&self, chunk_type: &str) -> Option<&Chunk> {
-        if let Some(i) = self
-            .chunks()
-            .iter()
-            .position(|chunk| chunk.chunk_type().str() == chunk_type)
-        {
+        if let Some(i) = self.position(chunk_type) {
             return Some(&self.chunks()[i]);
         }
 
         return None;
     }
 
+    // every chunk of `chunk_type` with its index in the file
+    pub fn search_chunks(&self, chunk_type: &str) -> Vec<(usize, &Chunk)> {
+        return self
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.chunk_type().str() == chunk_type)
+            .collect();
+    }
+
+    // the `index`-th chunk of `chunk_type`, counting from 0, with its index in the file
+    pub fn chunk_occurrence(&self, chunk_type: &str, index: usize) -> Result<(usize, &Chunk)> {
+        let found = self.search_chunks(chunk_type);
+        if found.is_empty() {
+            return Err(Error::ChunkNotFound(String::from(chunk_type)));
+        }
+
+        match found.get(index) {
+            Some(&(i, chunk)) => return Ok((i, chunk)),
+            None => {
+                return Err(Error::InvalidOccurrence {
+                    chunk_type: String::from(chunk_type),
+                    index,
+                    count: found.len(),
+                })
+            }
+        }
+    }
+
+    // byte offset of every chunk in the file
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        let mut offset = Self::STANDARD_HEADER.len();
+        let mut offsets: Vec<usize> = Vec::with_capacity(self.chunks().len());
+        for chunk in self.chunks() {
+            offsets.push(offset);
+            // length, type and CRC around the data
+            offset += 12 + chunk.length() as usize;
+        }
+
+        return offsets;
+    }
+
+    // the length of `bytes()`, without serializing the file
+    pub fn byte_length(&self) -> usize {
+        return self
+            .chunks()
+            .iter()
+            .fold(Self::STANDARD_HEADER.len(), |length, chunk| {
+                length + 12 + chunk.length() as usize
+            });
+    }
+
+    pub fn header_info(&self) -> Result<ImageHeader> {
+        match self.search_chunk(ImageHeader::CHUNK_TYPE) {
+            Some(chunk) => return ImageHeader::from_chunk(chunk),
+            None => return Err(Error::ChunkNotFound(String::from(ImageHeader::CHUNK_TYPE))),
+        }
+    }
+
+    // concatenation of every IDAT chunk's data, i.e. the zlib stream of the image
+    pub fn compressed_image_data(&self) -> Vec<u8> {
+        return self
+            .chunks()
+            .iter()
+            .filter(|chunk| chunk.chunk_type().str() == "IDAT")
+            .flat_map(|chunk| chunk.data().iter().copied())
+            .collect();
+    }
+
+    // inflated IDAT stream, failing if it would grow beyond `limit` bytes
+    pub fn image_data(&self, limit: usize) -> Result<Vec<u8>> {
+        if self.search_chunk("IDAT").is_none() {
+            return Err(Error::ChunkNotFound(String::from("IDAT")));
+        }
+
+        return inflate::zlib_decompress(&self.compressed_image_data(), limit);
+    }
+
+    // re-encodes the pixel data from `image`, keeping every chunk that does not describe it
+    pub fn replace_image(&mut self, image: &Image, level: CompressionLevel) -> Result<()> {
+        let encoded = image.to_png(level)?;
+        let same_format = match self.header_info() {
+            Ok(header) => {
+                header.color_type() == image.color_type() && header.bit_depth() == image.bit_depth()
+            }
+            Err(_) => false,
+        };
+        // palette-dependent chunks are only meaningful for the original pixel format
+        let replaced: &[&str] = if same_format {
+            &["IHDR", "IDAT"]
+        } else {
+            &["IHDR", "PLTE", "tRNS", "IDAT", "bKGD", "hIST", "sBIT"]
+        };
+
+        // IHDR of the new image, then its IDAT chunks preceded by PLTE/tRNS for a new format
+        let mut chunks: Vec<Chunk> = vec![encoded.chunks()[0].clone()];
+        let mut image_chunks: Vec<Chunk> = encoded.chunks()[1..]
+            .iter()
+            .filter(|chunk| {
+                let chunk_type = chunk.chunk_type().str();
+                return chunk_type == "IDAT" || (!same_format && chunk_type != "IEND");
+            })
+            .cloned()
+            .collect();
+
+        // the image data goes where the first IDAT was, or before IEND if there was none
+        let at = self
+            .position("IDAT")
+            .or_else(|| self.position("IEND"))
+            .unwrap_or(self.m_chunks.len());
+        for (i, chunk) in std::mem::take(&mut self.m_chunks).into_iter().enumerate() {
+            if i == at {
+                chunks.append(&mut image_chunks);
+            }
+            if !replaced.contains(&chunk.chunk_type().str()) {
+                chunks.push(chunk);
+            }
+        }
+        chunks.append(&mut image_chunks);
+
+        self.m_chunks = chunks;
+        return Ok(());
+    }
+
+    // frames of an APNG, failing with `ChunkNotFound` for a still image
+    pub fn animation(&self) -> Result<Animation> {
+        return Animation::from_png(self);
+    }
+
+    pub fn extract_frame(&self, index: usize) -> Result<Png> {
+        return apng::extract_frame(self, index);
+    }
+
+    // returns how many frames were removed, which repeated indices do not add to
+    pub fn drop_frames(&mut self, indices: &[usize]) -> Result<usize> {
+        let (png, dropped) = apng::drop_frames(self, indices)?;
+        *self = png;
+        return Ok(dropped);
+    }
+
+    // every chunk whose stored crc disagrees with its type and data
+    pub fn crc_mismatches(&self) -> Vec<CrcMismatch> {
+        return repair::crc_mismatches(self);
+    }
+
+    // fixes the bad crcs, returning what they were; see `repair::repair_crcs`
+    pub fn repair_crcs(&mut self, force: bool) -> CrcRepair {
+        let (png, repair) = repair::repair_crcs(self, force);
+        *self = png;
+        return repair;
+    }
+
+    // checks chunk ordering and multiplicity against the PNG spec
+    pub fn validate(&self) -> Vec<Violation> {
+        return validation::validate(self.chunks());
+    }
+
+    pub fn text_entries(&self) -> Result<Vec<TextEntry>> {
+        let mut entries: Vec<TextEntry> = Vec::new();
+        for chunk in self.chunks() {
+            if let Some(entry) = TextEntry::from_chunk(chunk)? {
+                entries.push(entry);
+            }
+        }
+
+        return Ok(entries);
+    }
+
+    // removes every tEXt, zTXt and iTXt chunk with `keyword`, returning how many were removed
+    pub fn remove_text(&mut self, keyword: &str) -> usize {
+        let len = self.m_chunks.len();
+        self.m_chunks
+            .retain(|chunk| text_chunk::chunk_keyword(chunk).as_deref() != Some(keyword));
+
+        return len - self.m_chunks.len();
+    }
+
+    // replaces any existing entries with the same keyword
+    pub fn set_text(&mut self, entry: &TextEntry) -> Result<()> {
+        let chunk = entry.to_chunk()?;
+        self.remove_text(entry.keyword());
+        self.insert_before_iend(chunk);
+
+        return Ok(());
+    }
+
+    // IHDR fields (null if missing or invalid) and every chunk with its index and byte offset
+    pub fn to_json(&self, data_encoding: Option<Encoding>) -> Json {
+        let header = match self.header_info() {
+            Ok(header) => header.to_json(),
+            Err(_) => Json::Null,
+        };
+
+        let offsets = self.chunk_offsets();
+        let mut chunks: Vec<Json> = Vec::with_capacity(self.chunks().len());
+        for (i, chunk) in self.chunks().iter().enumerate() {
+            let mut fields = vec![
+                (String::from("index"), Json::Number(i as u64)),
+                (String::from("offset"), Json::Number(offsets[i] as u64)),
+            ];
+            if let Json::Object(chunk_fields) = chunk.to_json(data_encoding) {
+                fields.extend(chunk_fields);
+            }
+            chunks.push(Json::Object(fields));
+        }
+
+        return Json::object(vec![("header", header), ("chunks", Json::Array(chunks))]);
+    }
+
+    // one line per chunk with its index, byte offset, property flags, length and crc status
+    pub fn summary_table(&self) -> String {
+        let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+        let mut table = format!(
+            "{:>5}  {:>10}  {:<4}  {:<8}  {:<6}  {:<4}  {:>10}  {}\n",
+            "index", "offset", "type", "critical", "public", "safe", "length", "crc"
+        );
+        let offsets = self.chunk_offsets();
+        for (i, chunk) in self.chunks().iter().enumerate() {
+            let chunk_type = chunk.chunk_type();
+            table.push_str(&format!(
+                "{:>5}  {:>10}  {:<4}  {:<8}  {:<6}  {:<4}  {:>10}  {:08x} {}\n",
+                i,
+                offsets[i],
+                chunk_type.str(),
+                yes_no(chunk_type.is_critical()),
+                yes_no(chunk_type.is_public()),
+                yes_no(chunk_type.is_safe_to_copy()),
+                chunk.length(),
+                chunk.crc(),
+                if chunk.has_valid_crc() { "ok" } else { "bad" }
+            ));
+        }
+        table.push_str(&format!(
+            "{} chunks, {} bytes\n",
+            self.chunks().len(),
+            self.byte_length()
+        ));
+
+        return table;
+    }
+
     pub fn bytes(&self) -> Vec<u8> {
         let bytes: Vec<u8> = Self::STANDARD_HEADER
             .iter()
             .copied()
-            .chain(self.chunks().into_iter().flat_map(|chunk| chunk.bytes()))
+            .chain(self.chunks().iter().flat_map(|chunk| chunk.bytes()))
             .collect();
         return bytes;
     }
@@ -97,22 +472,25 @@ impl fmt::Display for Png {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Png: {{\n\tHeader: {:?},\n\tChunks: {{\n", self.header())?;
         for chunk in self.chunks() {
-            write!(f, "\t\t{},\n", chunk)?;
+            writeln!(f, "\t\t{},", chunk)?;
         }
-        write!(f, "\t}}\n}}\n")
+        writeln!(f, "\t}}\n}}")
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::image_header::ColorType;
+    use crate::text_chunk::{CompressedTextChunk, TextChunk};
 
     #[test]
     pub fn test_png_from_bytes() {
-        let mut chunks = Vec::new();
-        chunks.push(Chunk::from_str("FrSt", "I am the first chunk").unwrap());
-        chunks.push(Chunk::from_str("miDl", "I am another chunk").unwrap());
-        chunks.push(Chunk::from_str("LASt", "I am the last chunk").unwrap());
+        let chunks = vec![
+            Chunk::from_str("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_str("miDl", "I am another chunk").unwrap(),
+            Chunk::from_str("LASt", "I am the last chunk").unwrap(),
+        ];
 
         let chunk_bytes: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.bytes()).collect();
 
@@ -128,10 +506,11 @@ mod tests {
 
     #[test]
     pub fn test_png_from_chunks() {
-        let mut chunks = Vec::new();
-        chunks.push(Chunk::from_str("FrSt", "I am the first chunk").unwrap());
-        chunks.push(Chunk::from_str("miDl", "I am another chunk").unwrap());
-        chunks.push(Chunk::from_str("LASt", "I am the last chunk").unwrap());
+        let chunks = vec![
+            Chunk::from_str("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_str("miDl", "I am another chunk").unwrap(),
+            Chunk::from_str("LASt", "I am the last chunk").unwrap(),
+        ];
 
         let png = Png::from_chunks(chunks);
         assert_eq!(png.chunks().len(), 3);
@@ -139,10 +518,11 @@ mod tests {
 
     #[test]
     pub fn test_png_invalid_header() {
-        let mut chunks = Vec::new();
-        chunks.push(Chunk::from_str("FrSt", "I am the first chunk").unwrap());
-        chunks.push(Chunk::from_str("miDl", "I am another chunk").unwrap());
-        chunks.push(Chunk::from_str("LASt", "I am the last chunk").unwrap());
+        let chunks = vec![
+            Chunk::from_str("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_str("miDl", "I am another chunk").unwrap(),
+            Chunk::from_str("LASt", "I am the last chunk").unwrap(),
+        ];
 
         let chunk_bytes: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.bytes()).collect();
 
@@ -153,15 +533,16 @@ mod tests {
             .collect();
 
         let png_res = Png::from_bytes(&bytes);
-        assert!(png_res.is_err());
+        assert!(matches!(png_res, Err(Error::InvalidSignature)));
     }
 
     #[test]
     fn test_invalid_chunk() {
-        let mut chunks = Vec::new();
-        chunks.push(Chunk::from_str("FrSt", "I am the first chunk").unwrap());
-        chunks.push(Chunk::from_str("miDl", "I am another chunk").unwrap());
-        chunks.push(Chunk::from_str("LASt", "I am the last chunk").unwrap());
+        let chunks = vec![
+            Chunk::from_str("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_str("miDl", "I am another chunk").unwrap(),
+            Chunk::from_str("LASt", "I am the last chunk").unwrap(),
+        ];
 
         let mut chunk_bytes: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.bytes()).collect();
 
@@ -185,6 +566,385 @@ mod tests {
         assert!(png_res.is_err());
     }
 
+    #[test]
+    pub fn test_png_truncated_chunk() {
+        let bytes = &PNG_FILE[..PNG_FILE.len() - 3];
+        let png_res = Png::from_bytes(bytes);
+        assert!(matches!(
+            png_res,
+            Err(Error::TruncatedChunk {
+                offset: 4791,
+                needed: 12,
+                available: 9
+            })
+        ));
+
+        let bytes = &PNG_FILE[..40];
+        let png_res = Png::from_bytes(bytes);
+        assert!(matches!(
+            png_res,
+            Err(Error::TruncatedChunk {
+                offset: 33,
+                needed: 12,
+                available: 7
+            })
+        ));
+    }
+
+    #[test]
+    pub fn test_png_invalid_length() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8..12].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        let png_res = Png::from_bytes(&bytes);
+        assert!(matches!(
+            png_res,
+            Err(Error::InvalidLength {
+                offset: 8,
+                length: 0xFFFFFFFF
+            })
+        ));
+
+        bytes[8..12].copy_from_slice(&[0x7F, 0xFF, 0xFF, 0xFF]);
+        let png_res = Png::from_bytes(&bytes);
+        assert!(matches!(
+            png_res,
+            Err(Error::TruncatedChunk { offset: 8, .. })
+        ));
+    }
+
+    #[test]
+    pub fn test_png_malformed_inputs() {
+        // every prefix of a valid file must fail cleanly (or parse if it ends on a chunk boundary)
+        for end in 0..PNG_FILE.len() {
+            let _ = Png::from_bytes(&PNG_FILE[..end]);
+        }
+
+        // signature followed by garbage of every small length
+        for extra in 1..32 {
+            let mut bytes = Png::STANDARD_HEADER.to_vec();
+            bytes.resize(8 + extra, 0xFF);
+            assert!(Png::from_bytes(&bytes).is_err());
+        }
+
+        // deterministic byte mutations across the whole file
+        let mut seed: u32 = 0x1234_5678;
+        for _ in 0..2000 {
+            let mut bytes = PNG_FILE.to_vec();
+            for _ in 0..4 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let idx = (seed as usize) % bytes.len();
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                bytes[idx] = (seed >> 16) as u8;
+            }
+            let _ = Png::from_bytes(&bytes);
+        }
+    }
+
+    fn chunk_types(png: &Png) -> Vec<&str> {
+        return png
+            .chunks()
+            .iter()
+            .map(|chunk| chunk.chunk_type().str())
+            .collect();
+    }
+
+    fn minimal_png() -> Png {
+        let chunks = vec![
+            Chunk::from_str("IHDR", "header").unwrap(),
+            Chunk::from_str("IDAT", "first").unwrap(),
+            Chunk::from_str("IDAT", "second").unwrap(),
+            Chunk::from_str("IEND", "").unwrap(),
+        ];
+        return Png::from_chunks(chunks);
+    }
+
+    #[test]
+    pub fn test_png_insert_positions() {
+        let mut png = minimal_png();
+        png.insert_before_iend(Chunk::from_str("ruSt", "a").unwrap());
+        png.insert_after_ihdr(Chunk::from_str("hiDe", "b").unwrap())
+            .unwrap();
+        png.insert_before_idat(Chunk::from_str("tEXt", "c").unwrap())
+            .unwrap();
+        png.insert_chunk(0, Chunk::from_str("zeRo", "d").unwrap())
+            .unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            vec!["zeRo", "IHDR", "hiDe", "tEXt", "IDAT", "IDAT", "ruSt", "IEND"]
+        );
+
+        png.insert_after("ruSt", Chunk::from_str("afTr", "e").unwrap())
+            .unwrap();
+        png.insert_before("hiDe", Chunk::from_str("beFr", "f").unwrap())
+            .unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            vec!["zeRo", "IHDR", "beFr", "hiDe", "tEXt", "IDAT", "IDAT", "ruSt", "afTr", "IEND"]
+        );
+    }
+
+    #[test]
+    pub fn test_png_search_chunks() {
+        let mut png = minimal_png();
+        for msg in ["a", "b", "c"] {
+            png.insert_before_iend(Chunk::from_str("ruSt", msg).unwrap());
+        }
+
+        let found = png.search_chunks("ruSt");
+        let indices: Vec<usize> = found.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, vec![3, 4, 5]);
+        assert_eq!(found[2].1.data(), b"c");
+        assert_eq!(png.search_chunks("IDAT").len(), 2);
+        assert!(png.search_chunks("teSt").is_empty());
+    }
+
+    #[test]
+    pub fn test_png_delete_chunks() {
+        let mut png = minimal_png();
+        for msg in ["a", "b", "c", "d"] {
+            png.insert_before_iend(Chunk::from_str("ruSt", msg).unwrap());
+        }
+
+        let deleted = png
+            .delete_chunks("ruSt", ChunkSelection::Occurrence(1))
+            .unwrap();
+        assert_eq!(deleted[0].data(), b"b");
+        assert!(matches!(
+            png.delete_chunks("ruSt", ChunkSelection::Occurrence(3)),
+            Err(Error::InvalidOccurrence {
+                index: 3,
+                count: 3,
+                ..
+            })
+        ));
+
+        let is_d = |chunk: &Chunk| chunk.data() == b"d";
+        let deleted = png
+            .delete_chunks("ruSt", ChunkSelection::Matching(&is_d))
+            .unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert!(matches!(
+            png.delete_chunks("ruSt", ChunkSelection::Matching(&is_d)),
+            Err(Error::ChunkNotFound(_))
+        ));
+
+        let deleted = png.delete_chunks("ruSt", ChunkSelection::All).unwrap();
+        let data: Vec<&[u8]> = deleted.iter().map(|chunk| chunk.data()).collect();
+        assert_eq!(data, vec![b"a", b"c"]);
+        assert_eq!(chunk_types(&png), vec!["IHDR", "IDAT", "IDAT", "IEND"]);
+        assert!(matches!(
+            png.delete_chunks("ruSt", ChunkSelection::All),
+            Err(Error::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_png_chunk_position_from_str() {
+        for (s, position) in [
+            ("end", ChunkPosition::End),
+            ("before-iend", ChunkPosition::BeforeIend),
+            ("after-ihdr", ChunkPosition::AfterIhdr),
+            ("before-idat", ChunkPosition::BeforeIdat),
+            ("index:3", ChunkPosition::Index(3)),
+            ("before:tEXt", ChunkPosition::Before(String::from("tEXt"))),
+            ("after:PLTE", ChunkPosition::After(String::from("PLTE"))),
+        ] {
+            assert_eq!(ChunkPosition::from_str(s).unwrap(), position);
+        }
+        for s in ["", "start", "index:x", "index:-1", "over:IDAT"] {
+            assert!(ChunkPosition::from_str(s).is_err());
+        }
+    }
+
+    #[test]
+    pub fn test_png_insert_errors() {
+        let mut png = minimal_png();
+        let res = png.insert_chunk(5, Chunk::from_str("ruSt", "a").unwrap());
+        assert!(matches!(res, Err(Error::InvalidIndex { index: 5, len: 4 })));
+
+        let res = png.insert_before("noPe", Chunk::from_str("ruSt", "a").unwrap());
+        assert!(matches!(res, Err(Error::ChunkNotFound(_))));
+
+        let mut png = Png::from_chunks(Vec::new());
+        png.insert_before_iend(Chunk::from_str("ruSt", "a").unwrap());
+        assert_eq!(chunk_types(&png), vec!["ruSt"]);
+        let res = png.insert_chunk_at(
+            &ChunkPosition::AfterIhdr,
+            Chunk::from_str("ruSt", "a").unwrap(),
+        );
+        assert!(matches!(res, Err(Error::ChunkNotFound(_))));
+    }
+
+    #[test]
+    pub fn test_png_text_entries() {
+        let mut png = minimal_png();
+        let entry = TextEntry::Text(TextChunk::new("Author", "me").unwrap());
+        png.set_text(&entry).unwrap();
+        let entry = TextEntry::Compressed(CompressedTextChunk::new("Comment", "hi").unwrap());
+        png.set_text(&entry).unwrap();
+        let entry = TextEntry::Text(TextChunk::new("Author", "you").unwrap());
+        png.set_text(&entry).unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            vec!["IHDR", "IDAT", "IDAT", "zTXt", "tEXt", "IEND"]
+        );
+
+        let entries = png.text_entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].keyword(), "Author");
+        assert_eq!(entries[1].text(), "you");
+
+        assert_eq!(png.remove_text("Comment"), 1);
+        assert_eq!(png.remove_text("Comment"), 0);
+        assert_eq!(png.text_entries().unwrap().len(), 1);
+    }
+
+    #[test]
+    pub fn test_png_header_info() {
+        let png = Png::from_bytes(&PNG_FILE).unwrap();
+        let header = png.header_info().unwrap();
+        assert_eq!(header.width(), 50);
+        assert_eq!(header.height(), 50);
+        assert_eq!(header.bit_depth(), 8);
+        assert_eq!(header.color_type(), ColorType::Rgba);
+        assert!(!header.is_interlaced());
+
+        let png = Png::from_chunks(Vec::new());
+        assert!(matches!(png.header_info(), Err(Error::ChunkNotFound(_))));
+    }
+
+    #[test]
+    pub fn test_png_image_data() {
+        let png = Png::from_bytes(&PNG_FILE).unwrap();
+        assert_eq!(png.compressed_image_data().len(), 4681);
+
+        // 50 rows of one filter byte and 50 RGBA pixels
+        let data = png.image_data(inflate::DEFAULT_OUTPUT_LIMIT).unwrap();
+        assert_eq!(data.len(), 50 * (1 + 50 * 4));
+        assert!(data.chunks(201).all(|row| row[0] <= 4));
+
+        assert!(matches!(
+            png.image_data(1000),
+            Err(Error::OutputLimitExceeded(1000))
+        ));
+        assert!(matches!(
+            Png::from_chunks(Vec::new()).image_data(1000),
+            Err(Error::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_png_decode_image() {
+        let png = Png::from_bytes(&PNG_FILE).unwrap();
+        let image = Image::from_png(&png, inflate::DEFAULT_OUTPUT_LIMIT).unwrap();
+
+        assert_eq!((image.width(), image.height()), (50, 50));
+        assert_eq!(image.color_type(), ColorType::Rgba);
+        assert_eq!(image.pixels().len(), 50 * 50 * 4);
+        let center: Vec<u16> = (0..4)
+            .map(|channel| image.sample(25, 25, channel))
+            .collect();
+        assert_eq!(center, [240, 240, 240, 255]);
+        let total: u64 = image.pixels().iter().map(|byte| *byte as u64).sum();
+        assert_eq!(total, 680403);
+    }
+
+    #[test]
+    pub fn test_png_replace_image() {
+        let mut png = Png::from_bytes(&PNG_FILE).unwrap();
+        let mut image = Image::from_png(&png, inflate::DEFAULT_OUTPUT_LIMIT).unwrap();
+        image.pixels_mut()[0] ^= 1;
+        png.replace_image(&image, CompressionLevel::Fast).unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "RuSt", "IEND"]
+        );
+        assert_eq!(
+            Image::from_png(&png, inflate::DEFAULT_OUTPUT_LIMIT).unwrap(),
+            image
+        );
+
+        // a new pixel format also drops the chunks tied to the old palette
+        let mut indexed = Image::new(2, 2, ColorType::Indexed, 8, vec![0, 1, 1, 0]).unwrap();
+        indexed
+            .set_palette(vec![[0, 0, 0], [255, 255, 255]])
+            .unwrap();
+        let mut png = indexed.to_png(CompressionLevel::Fast).unwrap();
+        png.insert_after("PLTE", Chunk::from_str("bKGD", "\0").unwrap())
+            .unwrap();
+        png.insert_after_ihdr(Chunk::from_str("tEXt", "Title\0dice").unwrap())
+            .unwrap();
+        let truecolor = indexed.to_truecolor().unwrap();
+        png.replace_image(&truecolor, CompressionLevel::Fast)
+            .unwrap();
+        assert_eq!(chunk_types(&png), ["IHDR", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(
+            Image::from_png(&png, inflate::DEFAULT_OUTPUT_LIMIT).unwrap(),
+            truecolor
+        );
+    }
+
+    #[test]
+    pub fn test_png_to_json() {
+        let json = minimal_png().to_json(Some(Encoding::Hex)).to_compact();
+        assert!(json.starts_with(r#"{"header":null,"chunks":[{"index":0,"offset":8,"type":"IHDR","critical":true,"public":true,"reserved_bit_valid":true,"safe_to_copy":false,"length":6,"#));
+        assert!(json.contains(r#"{"index":2,"offset":43,"type":"IDAT","#));
+        assert!(json.contains(r#""data":"7365636f6e64"}"#));
+
+        let png = Png::from_bytes(&PNG_FILE).unwrap();
+        let json = png.to_json(None).to_compact();
+        assert!(json.starts_with(r#"{"header":{"width":"#));
+        assert!(!json.contains(r#""data""#));
+    }
+
+    #[test]
+    pub fn test_png_chunk_occurrence() {
+        let mut png = minimal_png();
+        png.insert_before_iend(Chunk::from_str("RuSt", "one").unwrap());
+        png.insert_before_iend(Chunk::from_str("RuSt", "again").unwrap());
+
+        let (i, chunk) = png.chunk_occurrence("RuSt", 1).unwrap();
+        assert_eq!((i, chunk.data_str().unwrap()), (4, "again"));
+        assert_eq!(png.chunk_offsets(), vec![8, 26, 43, 61, 76, 93]);
+        assert_eq!(png.byte_length(), png.bytes().len());
+        assert!(matches!(
+            png.chunk_occurrence("RuSt", 2),
+            Err(Error::InvalidOccurrence { count: 2, .. })
+        ));
+        assert!(matches!(
+            png.chunk_occurrence("teXt", 0),
+            Err(Error::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    pub fn test_png_summary_table() {
+        let table = minimal_png().summary_table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].starts_with("index      offset  type  critical"));
+        assert!(
+            lines[1].starts_with("    0           8  IHDR  yes       yes     no             6  ")
+        );
+        assert!(lines[1].ends_with(" ok"));
+        assert_eq!(
+            lines[5],
+            format!("4 chunks, {} bytes", minimal_png().bytes().len())
+        );
+    }
+
+    #[test]
+    pub fn test_png_validate() {
+        let png = Png::from_bytes(&PNG_FILE).unwrap();
+        assert_eq!(
+            png.validate(),
+            vec![Violation::UnknownCriticalChunk {
+                chunk_type: String::from("RuSt"),
+                index: 5,
+            }]
+        );
+    }
+
     #[test]
     pub fn test_png_from_file() {
         let png_res = Png::from_bytes(&PNG_FILE);
@@ -0,0 +1,195 @@
+use std::io::{ErrorKind, Read};
+
+use crate::chunk::{Chunk, ParseMode};
+use crate::chunk_type::ChunkType;
+use crate::error::{Error, Result};
+use crate::png::Png;
+
+pub struct PngReader<R: Read> {
+    m_reader: R,
+    m_offset: usize,
+    m_mode: ParseMode,
+    m_done: bool,
+}
+
+impl<R: Read> PngReader<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        return Self::with_mode(reader, ParseMode::Strict);
+    }
+
+    pub fn with_mode(mut reader: R, mode: ParseMode) -> Result<Self> {
+        let mut header = [0; 8];
+        let n = Self::read_full(&mut reader, &mut header)?;
+        if n < 8 || header != Png::STANDARD_HEADER {
+            return Err(Error::InvalidSignature);
+        }
+
+        return Ok(Self {
+            m_reader: reader,
+            m_offset: 8,
+            m_mode: mode,
+            m_done: false,
+        });
+    }
+
+    pub fn offset(&self) -> usize {
+        return self.m_offset;
+    }
+
+    pub fn into_inner(self) -> R {
+        return self.m_reader;
+    }
+
+    // returns `None` once the stream ends cleanly on a chunk boundary
+    pub fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        if self.m_done {
+            return Ok(None);
+        }
+
+        let res = self.read_chunk_inner();
+        if !matches!(res, Ok(Some(_))) {
+            self.m_done = true;
+        }
+        return res;
+    }
+
+    fn read_chunk_inner(&mut self) -> Result<Option<Chunk>> {
+        let offset = self.m_offset;
+
+        // length(4 bytes) + chunk_type(4 bytes)
+        let mut prefix = [0; 8];
+        let n = Self::read_full(&mut self.m_reader, &mut prefix)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        if n < 8 {
+            return Err(Error::TruncatedChunk {
+                offset,
+                needed: 12,
+                available: n,
+            });
+        }
+
+        let length = u32::from_be_bytes(prefix[0..4].try_into().unwrap());
+        if length > Chunk::MAX_LENGTH {
+            return Err(Error::InvalidLength { offset, length });
+        }
+        let chunk_type = ChunkType::from_bytes(&prefix[4..8])?;
+
+        // read through `take` so a hostile length cannot force a huge allocation up front
+        let mut data = Vec::new();
+        let n = (&mut self.m_reader)
+            .take(length as u64)
+            .read_to_end(&mut data)?;
+        if n < length as usize {
+            return Err(Error::TruncatedChunk {
+                offset,
+                needed: length as usize + 12,
+                available: n + 8,
+            });
+        }
+
+        let mut crc = [0; 4];
+        let n = Self::read_full(&mut self.m_reader, &mut crc)?;
+        if n < 4 {
+            return Err(Error::TruncatedChunk {
+                offset,
+                needed: length as usize + 12,
+                available: length as usize + 8 + n,
+            });
+        }
+
+        let chunk = Chunk::from_parts(chunk_type, data, u32::from_be_bytes(crc), self.m_mode)?;
+        self.m_offset += length as usize + 12;
+        return Ok(Some(chunk));
+    }
+
+    // like `read_exact`, but reports how many bytes were read before EOF
+    fn read_full(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            match reader.read(&mut buf[n..]) {
+                Ok(0) => break,
+                Ok(m) => n += m,
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(Error::Io(err)),
+            }
+        }
+        return Ok(n);
+    }
+}
+
+impl<R: Read> Iterator for PngReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        return self.read_chunk().transpose();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_ref::test_png_bytes;
+
+    #[test]
+    pub fn test_reader_chunks() {
+        let bytes = test_png_bytes();
+        let reader = PngReader::new(&bytes[..]).unwrap();
+        let chunks: Vec<Chunk> = reader.collect::<Result<Vec<Chunk>>>().unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].chunk_type().str(), "miDl");
+        assert_eq!(chunks[2].data_str().unwrap(), "I am the last chunk");
+        assert_eq!(Png::from_chunks(chunks), Png::from_bytes(&bytes).unwrap());
+    }
+
+    #[test]
+    pub fn test_reader_invalid_signature() {
+        let mut bytes = test_png_bytes();
+        bytes[0] = 13;
+        assert!(matches!(
+            PngReader::new(&bytes[..]),
+            Err(Error::InvalidSignature)
+        ));
+        assert!(matches!(
+            PngReader::new(&bytes[..4]),
+            Err(Error::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    pub fn test_reader_truncated() {
+        let bytes = test_png_bytes();
+        for end in 9..bytes.len() {
+            let reader = PngReader::new(&bytes[..end]).unwrap();
+            let res: Result<Vec<Chunk>> = reader.collect();
+            if end == 40 || end == 70 {
+                assert!(res.is_ok());
+            } else {
+                assert!(matches!(res, Err(Error::TruncatedChunk { .. })));
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_reader_bad_crc() {
+        let mut bytes = test_png_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+
+        let mut reader = PngReader::new(&bytes[..]).unwrap();
+        assert!(reader.read_chunk().unwrap().is_some());
+        assert!(reader.read_chunk().unwrap().is_some());
+        assert!(matches!(reader.read_chunk(), Err(Error::InvalidCrc { .. })));
+        assert!(reader.read_chunk().unwrap().is_none());
+
+        let reader = PngReader::with_mode(&bytes[..], ParseMode::Lenient).unwrap();
+        let chunks: Vec<Chunk> = reader.collect::<Result<Vec<Chunk>>>().unwrap();
+        assert!(!chunks[2].has_valid_crc());
+        assert_eq!(
+            chunks[2].with_valid_crc(),
+            Chunk::from_str("LASt", "I am the last chunk").unwrap()
+        );
+    }
+}
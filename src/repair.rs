@@ -0,0 +1,182 @@
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+// a chunk whose stored crc does not match its type and data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrcMismatch {
+    m_index: usize,
+    m_chunk_type: String,
+    m_critical: bool,
+    m_stored: u32,
+    m_computed: u32,
+}
+
+impl CrcMismatch {
+    fn new(index: usize, chunk: &Chunk) -> Self {
+        return Self {
+            m_index: index,
+            m_chunk_type: String::from(chunk.chunk_type().str()),
+            m_critical: chunk.chunk_type().is_critical(),
+            m_stored: chunk.crc(),
+            m_computed: chunk.with_valid_crc().crc(),
+        };
+    }
+
+    pub fn index(&self) -> usize {
+        return self.m_index;
+    }
+
+    pub fn chunk_type(&self) -> &str {
+        return &self.m_chunk_type;
+    }
+
+    pub fn is_critical(&self) -> bool {
+        return self.m_critical;
+    }
+
+    pub fn stored(&self) -> u32 {
+        return self.m_stored;
+    }
+
+    pub fn computed(&self) -> u32 {
+        return self.m_computed;
+    }
+}
+
+impl fmt::Display for CrcMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Chunk {} at index {} has CRC {:08x}, but its type and data give {:08x}.",
+            self.chunk_type(),
+            self.index(),
+            self.stored(),
+            self.computed()
+        )
+    }
+}
+
+// only a leniently parsed file can hold chunks with a bad crc
+pub(crate) fn crc_mismatches(png: &Png) -> Vec<CrcMismatch> {
+    return png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| !chunk.has_valid_crc())
+        .map(|(i, chunk)| CrcMismatch::new(i, chunk))
+        .collect();
+}
+
+// what `repair_crcs` did with each bad crc
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CrcRepair {
+    m_fixed: Vec<CrcMismatch>,
+    m_skipped: Vec<CrcMismatch>,
+}
+
+impl CrcRepair {
+    pub fn fixed(&self) -> &[CrcMismatch] {
+        return &self.m_fixed;
+    }
+
+    // critical chunks left with their bad crc
+    pub fn skipped(&self) -> &[CrcMismatch] {
+        return &self.m_skipped;
+    }
+}
+
+// recomputes every bad crc; a bad crc on a critical chunk more likely means damaged data than a
+// damaged crc, so those are skipped unless `force` is set
+pub(crate) fn repair_crcs(png: &Png, force: bool) -> (Png, CrcRepair) {
+    let mut repair = CrcRepair::default();
+    let mut chunks: Vec<Chunk> = Vec::new();
+    for (i, chunk) in png.chunks().iter().enumerate() {
+        if chunk.has_valid_crc() {
+            chunks.push(chunk.clone());
+            continue;
+        }
+
+        let mismatch = CrcMismatch::new(i, chunk);
+        if mismatch.is_critical() && !force {
+            chunks.push(chunk.clone());
+            repair.m_skipped.push(mismatch);
+        } else {
+            chunks.push(chunk.with_valid_crc());
+            repair.m_fixed.push(mismatch);
+        }
+    }
+
+    return (Png::from_chunks(chunks), repair);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ParseMode;
+    use crate::error::Error;
+
+    fn damaged_png_bytes() -> Vec<u8> {
+        let chunks = vec![
+            Chunk::from_str("IHDR", "header").unwrap(),
+            Chunk::from_str("teXt", "comment").unwrap(),
+            Chunk::from_str("IEND", "").unwrap(),
+        ];
+        let mut bytes = Png::from_chunks(chunks).bytes();
+        // last byte of the teXt crc
+        bytes[8 + 18 + 18] ^= 0x40;
+        return bytes;
+    }
+
+    #[test]
+    pub fn test_repair_ancillary_crc() {
+        let bytes = damaged_png_bytes();
+        assert!(matches!(
+            Png::from_bytes(&bytes),
+            Err(Error::InvalidCrc { .. })
+        ));
+
+        let mut png = Png::from_bytes_with_mode(&bytes, ParseMode::Lenient).unwrap();
+        let mismatches = png.crc_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(
+            (mismatches[0].index(), mismatches[0].chunk_type()),
+            (1, "teXt")
+        );
+        assert_eq!(mismatches[0].stored() ^ mismatches[0].computed(), 0x40);
+
+        let repair = png.repair_crcs(false);
+        assert_eq!(repair.fixed(), &mismatches[..]);
+        assert!(repair.skipped().is_empty());
+        assert!(png.crc_mismatches().is_empty());
+        assert_eq!(Png::from_bytes(&png.bytes()).unwrap(), png);
+    }
+
+    #[test]
+    pub fn test_repair_critical_crc() {
+        let mut bytes = damaged_png_bytes();
+        bytes[8 + 17] ^= 0x01;
+        let mut png = Png::from_bytes_with_mode(&bytes, ParseMode::Lenient).unwrap();
+
+        // the ancillary chunk is fixed, the critical one is left alone
+        let repair = png.repair_crcs(false);
+        assert_eq!(repair.fixed().len(), 1);
+        assert_eq!(repair.fixed()[0].chunk_type(), "teXt");
+        assert_eq!(repair.skipped().len(), 1);
+        assert_eq!(
+            (
+                repair.skipped()[0].index(),
+                repair.skipped()[0].chunk_type()
+            ),
+            (0, "IHDR")
+        );
+        assert_eq!(png.crc_mismatches(), repair.skipped());
+
+        let repair = png.repair_crcs(true);
+        assert_eq!(repair.fixed().len(), 1);
+        assert!(repair.fixed()[0].is_critical());
+        assert!(repair.skipped().is_empty());
+        assert!(png.crc_mismatches().is_empty());
+    }
+}
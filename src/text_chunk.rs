@@ -0,0 +1,485 @@
+use std::str::{from_utf8, FromStr};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::deflate::{self, CompressionLevel};
+use crate::error::{Error, Result};
+use crate::inflate;
+
+// guards zTXt/iTXt decompression against zlib bombs
+const MAX_TEXT_LEN: usize = 16 * 1024 * 1024;
+const COMPRESSION_LEVEL: CompressionLevel = CompressionLevel::Default;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    m_keyword: String,
+    m_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTextChunk {
+    m_keyword: String,
+    m_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternationalTextChunk {
+    m_keyword: String,
+    m_compressed: bool,
+    m_language_tag: String,
+    m_translated_keyword: String,
+    m_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextEntry {
+    Text(TextChunk),
+    Compressed(CompressedTextChunk),
+    International(InternationalTextChunk),
+}
+
+impl TextChunk {
+    pub const CHUNK_TYPE: &'static str = "tEXt";
+
+    pub fn new(keyword: &str, text: &str) -> Result<Self> {
+        check_keyword(keyword)?;
+        check_latin1_text(text)?;
+
+        return Ok(Self {
+            m_keyword: String::from(keyword),
+            m_text: String::from(text),
+        });
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        check_chunk_type(chunk, Self::CHUNK_TYPE)?;
+
+        let (keyword, rest) = split_keyword(chunk)?;
+        return Self::new(&keyword, &decode_latin1(rest));
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = encode_latin1(&self.m_keyword)?;
+        data.push(0);
+        data.extend(encode_latin1(&self.m_text)?);
+
+        return Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE)?, data);
+    }
+
+    pub fn keyword(&self) -> &str {
+        return &self.m_keyword;
+    }
+
+    pub fn text(&self) -> &str {
+        return &self.m_text;
+    }
+}
+
+impl CompressedTextChunk {
+    pub const CHUNK_TYPE: &'static str = "zTXt";
+
+    pub fn new(keyword: &str, text: &str) -> Result<Self> {
+        check_keyword(keyword)?;
+        check_latin1_text(text)?;
+
+        return Ok(Self {
+            m_keyword: String::from(keyword),
+            m_text: String::from(text),
+        });
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        check_chunk_type(chunk, Self::CHUNK_TYPE)?;
+
+        let (keyword, rest) = split_keyword(chunk)?;
+        match rest.split_first() {
+            Some((0, compressed)) => {
+                let text = decompress(compressed)?;
+                return Self::new(&keyword, &decode_latin1(&text));
+            }
+            Some(_) => return Err(malformed(chunk, "unknown compression method")),
+            None => return Err(malformed(chunk, "missing compression method")),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = encode_latin1(&self.m_keyword)?;
+        data.push(0);
+        data.push(0);
+        data.extend(compress(&encode_latin1(&self.m_text)?));
+
+        return Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE)?, data);
+    }
+
+    pub fn keyword(&self) -> &str {
+        return &self.m_keyword;
+    }
+
+    pub fn text(&self) -> &str {
+        return &self.m_text;
+    }
+}
+
+impl InternationalTextChunk {
+    pub const CHUNK_TYPE: &'static str = "iTXt";
+
+    pub fn new(
+        keyword: &str,
+        compressed: bool,
+        language_tag: &str,
+        translated_keyword: &str,
+        text: &str,
+    ) -> Result<Self> {
+        check_keyword(keyword)?;
+
+        // language tags are hyphen-separated ASCII words, e.g. "en-US"
+        if !language_tag
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        {
+            return Err(Error::InvalidLanguageTag(String::from(language_tag)));
+        }
+        if translated_keyword.contains('\0') || text.contains('\0') {
+            return Err(Error::InvalidText(String::from(
+                "text contains a null byte",
+            )));
+        }
+
+        return Ok(Self {
+            m_keyword: String::from(keyword),
+            m_compressed: compressed,
+            m_language_tag: String::from(language_tag),
+            m_translated_keyword: String::from(translated_keyword),
+            m_text: String::from(text),
+        });
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        check_chunk_type(chunk, Self::CHUNK_TYPE)?;
+
+        let (keyword, rest) = split_keyword(chunk)?;
+        if rest.len() < 2 {
+            return Err(malformed(chunk, "missing compression fields"));
+        }
+        let compressed = match (rest[0], rest[1]) {
+            (0, _) => false,
+            (1, 0) => true,
+            (1, _) => return Err(malformed(chunk, "unknown compression method")),
+            _ => return Err(malformed(chunk, "invalid compression flag")),
+        };
+
+        let mut fields = rest[2..].splitn(3, |b| *b == 0);
+        let language_tag = fields.next().unwrap();
+        let translated_keyword = match fields.next() {
+            Some(field) => field,
+            None => return Err(malformed(chunk, "missing language tag terminator")),
+        };
+        let text = match fields.next() {
+            Some(field) => field,
+            None => return Err(malformed(chunk, "missing translated keyword terminator")),
+        };
+
+        let text = if compressed {
+            decompress(text)?
+        } else {
+            text.to_vec()
+        };
+
+        let language_tag = from_utf8(language_tag).map_err(Error::InvalidUtf8)?;
+        let translated_keyword = from_utf8(translated_keyword).map_err(Error::InvalidUtf8)?;
+        let text = from_utf8(&text).map_err(Error::InvalidUtf8)?;
+
+        return Self::new(&keyword, compressed, language_tag, translated_keyword, text);
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = encode_latin1(&self.m_keyword)?;
+        data.push(0);
+        data.push(self.m_compressed as u8);
+        data.push(0);
+        data.extend_from_slice(self.m_language_tag.as_bytes());
+        data.push(0);
+        data.extend_from_slice(self.m_translated_keyword.as_bytes());
+        data.push(0);
+        if self.m_compressed {
+            data.extend(compress(self.m_text.as_bytes()));
+        } else {
+            data.extend_from_slice(self.m_text.as_bytes());
+        }
+
+        return Chunk::new(ChunkType::from_str(Self::CHUNK_TYPE)?, data);
+    }
+
+    pub fn keyword(&self) -> &str {
+        return &self.m_keyword;
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        return self.m_compressed;
+    }
+
+    pub fn language_tag(&self) -> &str {
+        return &self.m_language_tag;
+    }
+
+    pub fn translated_keyword(&self) -> &str {
+        return &self.m_translated_keyword;
+    }
+
+    pub fn text(&self) -> &str {
+        return &self.m_text;
+    }
+}
+
+impl TextEntry {
+    pub fn is_text_chunk_type(chunk_type: &str) -> bool {
+        return chunk_type == TextChunk::CHUNK_TYPE
+            || chunk_type == CompressedTextChunk::CHUNK_TYPE
+            || chunk_type == InternationalTextChunk::CHUNK_TYPE;
+    }
+
+    // returns `None` for chunks that are not textual
+    pub fn from_chunk(chunk: &Chunk) -> Result<Option<Self>> {
+        match chunk.chunk_type().str() {
+            TextChunk::CHUNK_TYPE => return Ok(Some(Self::Text(TextChunk::from_chunk(chunk)?))),
+            CompressedTextChunk::CHUNK_TYPE => {
+                return Ok(Some(Self::Compressed(CompressedTextChunk::from_chunk(
+                    chunk,
+                )?)))
+            }
+            InternationalTextChunk::CHUNK_TYPE => {
+                return Ok(Some(Self::International(
+                    InternationalTextChunk::from_chunk(chunk)?,
+                )))
+            }
+            _ => return Ok(None),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        match self {
+            Self::Text(text_chunk) => return text_chunk.to_chunk(),
+            Self::Compressed(text_chunk) => return text_chunk.to_chunk(),
+            Self::International(text_chunk) => return text_chunk.to_chunk(),
+        }
+    }
+
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            Self::Text(_) => return TextChunk::CHUNK_TYPE,
+            Self::Compressed(_) => return CompressedTextChunk::CHUNK_TYPE,
+            Self::International(_) => return InternationalTextChunk::CHUNK_TYPE,
+        }
+    }
+
+    pub fn keyword(&self) -> &str {
+        match self {
+            Self::Text(text_chunk) => return text_chunk.keyword(),
+            Self::Compressed(text_chunk) => return text_chunk.keyword(),
+            Self::International(text_chunk) => return text_chunk.keyword(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Text(text_chunk) => return text_chunk.text(),
+            Self::Compressed(text_chunk) => return text_chunk.text(),
+            Self::International(text_chunk) => return text_chunk.text(),
+        }
+    }
+}
+
+// reads the keyword without fully decoding the chunk, for matching by keyword
+pub(crate) fn chunk_keyword(chunk: &Chunk) -> Option<String> {
+    if !TextEntry::is_text_chunk_type(chunk.chunk_type().str()) {
+        return None;
+    }
+
+    return split_keyword(chunk).ok().map(|(keyword, _)| keyword);
+}
+
+// keywords are 1-79 printable Latin-1 characters without leading, trailing or consecutive spaces
+fn check_keyword(keyword: &str) -> Result<()> {
+    let len = keyword.chars().count();
+    let printable = keyword
+        .chars()
+        .all(|c| matches!(c as u32, 32..=126 | 161..=255));
+    if len == 0
+        || len > 79
+        || !printable
+        || keyword.starts_with(' ')
+        || keyword.ends_with(' ')
+        || keyword.contains("  ")
+    {
+        return Err(Error::InvalidKeyword(String::from(keyword)));
+    }
+
+    return Ok(());
+}
+
+fn check_latin1_text(text: &str) -> Result<()> {
+    if let Some(c) = text.chars().find(|c| *c as u32 > 255 || *c == '\0') {
+        return Err(Error::InvalidText(format!(
+            "{:?} is not a printable Latin-1 character",
+            c
+        )));
+    }
+
+    return Ok(());
+}
+
+fn check_chunk_type(chunk: &Chunk, chunk_type: &str) -> Result<()> {
+    if chunk.chunk_type().str() != chunk_type {
+        return Err(Error::UnexpectedChunkType {
+            expected: String::from(chunk_type),
+            found: String::from(chunk.chunk_type().str()),
+        });
+    }
+
+    return Ok(());
+}
+
+fn split_keyword(chunk: &Chunk) -> Result<(String, &[u8])> {
+    let data = chunk.data();
+    match data.iter().position(|b| *b == 0) {
+        Some(i) => return Ok((decode_latin1(&data[..i]), &data[(i + 1)..])),
+        None => return Err(malformed(chunk, "missing keyword terminator")),
+    }
+}
+
+fn malformed(chunk: &Chunk, reason: &str) -> Error {
+    return Error::MalformedChunk {
+        chunk_type: String::from(chunk.chunk_type().str()),
+        reason: String::from(reason),
+    };
+}
+
+fn decode_latin1(bytes: &[u8]) -> String {
+    return bytes.iter().map(|b| *b as char).collect();
+}
+
+fn encode_latin1(s: &str) -> Result<Vec<u8>> {
+    check_latin1_text(s)?;
+    return Ok(s.chars().map(|c| c as u8).collect());
+}
+
+fn compress(bytes: &[u8]) -> Vec<u8> {
+    return deflate::zlib_compress(bytes, COMPRESSION_LEVEL);
+}
+
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+    return inflate::zlib_decompress(bytes, MAX_TEXT_LEN);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_text_chunk() {
+        let text_chunk = TextChunk::new("Author", "Jos\u{e9} M\u{fc}ller").unwrap();
+        let chunk = text_chunk.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().str(), "tEXt");
+        assert_eq!(chunk.data(), b"Author\0Jos\xe9 M\xfcller");
+        assert_eq!(TextChunk::from_chunk(&chunk).unwrap(), text_chunk);
+    }
+
+    #[test]
+    pub fn test_compressed_text_chunk() {
+        let text = "Lorem ipsum dolor sit amet. ".repeat(20);
+        let text_chunk = CompressedTextChunk::new("Comment", &text).unwrap();
+        let chunk = text_chunk.to_chunk().unwrap();
+
+        assert_eq!(chunk.chunk_type().str(), "zTXt");
+        assert!(chunk.data().len() < text.len());
+        assert_eq!(&chunk.data()[..9], b"Comment\0\0");
+        assert_eq!(
+            CompressedTextChunk::from_chunk(&chunk).unwrap().text(),
+            text
+        );
+    }
+
+    #[test]
+    pub fn test_international_text_chunk() {
+        for compressed in [false, true] {
+            let text_chunk =
+                InternationalTextChunk::new("Title", compressed, "ja", "タイトル", "こんにちは")
+                    .unwrap();
+            let chunk = text_chunk.to_chunk().unwrap();
+
+            assert_eq!(chunk.chunk_type().str(), "iTXt");
+            let parsed = InternationalTextChunk::from_chunk(&chunk).unwrap();
+            assert_eq!(parsed, text_chunk);
+            assert_eq!(parsed.language_tag(), "ja");
+            assert_eq!(parsed.translated_keyword(), "タイトル");
+            assert_eq!(parsed.text(), "こんにちは");
+        }
+    }
+
+    #[test]
+    pub fn test_text_entry_from_chunk() {
+        let chunk = TextChunk::new("Author", "me").unwrap().to_chunk().unwrap();
+        let entry = TextEntry::from_chunk(&chunk).unwrap().unwrap();
+        assert_eq!(entry.keyword(), "Author");
+        assert_eq!(entry.text(), "me");
+        assert_eq!(entry.chunk_type(), "tEXt");
+        assert_eq!(chunk_keyword(&chunk).unwrap(), "Author");
+
+        let chunk = Chunk::from_str("RuSt", "not text").unwrap();
+        assert!(TextEntry::from_chunk(&chunk).unwrap().is_none());
+        assert!(chunk_keyword(&chunk).is_none());
+    }
+
+    #[test]
+    pub fn test_text_chunk_validation() {
+        assert!(matches!(
+            TextChunk::new("", "text"),
+            Err(Error::InvalidKeyword(_))
+        ));
+        assert!(matches!(
+            TextChunk::new(" Author", "text"),
+            Err(Error::InvalidKeyword(_))
+        ));
+        assert!(matches!(
+            TextChunk::new("Two  spaces", "text"),
+            Err(Error::InvalidKeyword(_))
+        ));
+        assert!(matches!(
+            TextChunk::new(&"k".repeat(80), "text"),
+            Err(Error::InvalidKeyword(_))
+        ));
+        assert!(matches!(
+            TextChunk::new("Title", "こんにちは"),
+            Err(Error::InvalidText(_))
+        ));
+        assert!(matches!(
+            InternationalTextChunk::new("Title", false, "en_US", "", ""),
+            Err(Error::InvalidLanguageTag(_))
+        ));
+
+        let chunk = Chunk::from_str("tEXt", "no terminator").unwrap();
+        assert!(matches!(
+            TextChunk::from_chunk(&chunk),
+            Err(Error::MalformedChunk { .. })
+        ));
+
+        let chunk = Chunk::from_str("zTXt", "Comment\0\0not zlib").unwrap();
+        assert!(matches!(
+            CompressedTextChunk::from_chunk(&chunk),
+            Err(Error::InvalidZlibHeader)
+        ));
+
+        let chunk = Chunk::from_str("iTXt", "Title\0\0\0en").unwrap();
+        assert!(matches!(
+            InternationalTextChunk::from_chunk(&chunk),
+            Err(Error::MalformedChunk { .. })
+        ));
+
+        let chunk = Chunk::from_str("tEXt", "Author\0me").unwrap();
+        assert!(matches!(
+            CompressedTextChunk::from_chunk(&chunk),
+            Err(Error::UnexpectedChunkType { .. })
+        ));
+    }
+}
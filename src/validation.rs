@@ -0,0 +1,355 @@
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::image_header::{ColorType, ImageHeader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    BeforePlte,
+    AfterPlte,
+    BeforeIdat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    MissingChunk(String),
+    IhdrNotFirst {
+        index: usize,
+    },
+    InvalidHeader(String),
+    DuplicateChunk {
+        chunk_type: String,
+        index: usize,
+    },
+    NonContiguousIdat {
+        index: usize,
+    },
+    ChunkAfterIend {
+        chunk_type: String,
+        index: usize,
+    },
+    MisplacedChunk {
+        chunk_type: String,
+        index: usize,
+        placement: Placement,
+    },
+    ForbiddenPlte {
+        index: usize,
+    },
+    ConflictingChunks {
+        chunk_type: String,
+        other: String,
+        index: usize,
+    },
+    UnknownCriticalChunk {
+        chunk_type: String,
+        index: usize,
+    },
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BeforePlte => write!(f, "before PLTE"),
+            Self::AfterPlte => write!(f, "after PLTE"),
+            Self::BeforeIdat => write!(f, "before the first IDAT"),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingChunk(chunk_type) => write!(f, "Missing required {} chunk.", chunk_type),
+            Self::IhdrNotFirst { index } => {
+                write!(f, "IHDR must be the first chunk, found at index {}.", index)
+            }
+            Self::InvalidHeader(reason) => write!(f, "Invalid IHDR chunk: {}", reason),
+            Self::DuplicateChunk { chunk_type, index } => write!(
+                f,
+                "Chunk {} at index {} may appear only once.",
+                chunk_type, index
+            ),
+            Self::NonContiguousIdat { index } => write!(
+                f,
+                "IDAT chunk at index {} is separated from the previous IDAT chunks.",
+                index
+            ),
+            Self::ChunkAfterIend { chunk_type, index } => write!(
+                f,
+                "Chunk {} at index {} appears after IEND.",
+                chunk_type, index
+            ),
+            Self::MisplacedChunk {
+                chunk_type,
+                index,
+                placement,
+            } => write!(
+                f,
+                "Chunk {} at index {} must appear {}.",
+                chunk_type, index, placement
+            ),
+            Self::ForbiddenPlte { index } => write!(
+                f,
+                "PLTE chunk at index {} is not allowed for grayscale images.",
+                index
+            ),
+            Self::ConflictingChunks {
+                chunk_type,
+                other,
+                index,
+            } => write!(
+                f,
+                "Chunk {} at index {} cannot appear together with {}.",
+                chunk_type, index, other
+            ),
+            Self::UnknownCriticalChunk { chunk_type, index } => write!(
+                f,
+                "Unknown critical chunk {} at index {}.",
+                chunk_type, index
+            ),
+        }
+    }
+}
+
+// chunks that may appear at most once
+const SINGLE_CHUNKS: [&str; 15] = [
+    "IHDR", "PLTE", "IEND", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "bKGD", "hIST", "tRNS", "pHYs",
+    "tIME", "eXIf", "acTL",
+];
+const BEFORE_PLTE_CHUNKS: [&str; 5] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB"];
+const AFTER_PLTE_CHUNKS: [&str; 3] = ["bKGD", "hIST", "tRNS"];
+const BEFORE_IDAT_CHUNKS: [&str; 11] = [
+    "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "bKGD", "hIST", "tRNS", "pHYs", "sPLT", "acTL",
+];
+const CRITICAL_CHUNKS: [&str; 4] = ["IHDR", "PLTE", "IDAT", "IEND"];
+
+pub(crate) fn validate(chunks: &[Chunk]) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = Vec::new();
+    let types: Vec<&str> = chunks
+        .iter()
+        .map(|chunk| chunk.chunk_type().str())
+        .collect();
+    let position = |chunk_type: &str| types.iter().position(|t| *t == chunk_type);
+
+    let plte = position("PLTE");
+    let first_idat = position("IDAT");
+    let iend = position("IEND");
+
+    match position("IHDR") {
+        None => violations.push(Violation::MissingChunk(String::from("IHDR"))),
+        Some(i) => {
+            if i != 0 {
+                violations.push(Violation::IhdrNotFirst { index: i });
+            }
+            match ImageHeader::from_chunk(&chunks[i]) {
+                Ok(header) => check_palette(&header, plte, &mut violations),
+                Err(err) => violations.push(Violation::InvalidHeader(err.to_string())),
+            }
+        }
+    }
+    if first_idat.is_none() {
+        violations.push(Violation::MissingChunk(String::from("IDAT")));
+    }
+    if iend.is_none() {
+        violations.push(Violation::MissingChunk(String::from("IEND")));
+    }
+
+    for (index, chunk_type) in types.iter().enumerate() {
+        let chunk_type = *chunk_type;
+
+        if SINGLE_CHUNKS.contains(&chunk_type) && position(chunk_type) != Some(index) {
+            violations.push(Violation::DuplicateChunk {
+                chunk_type: String::from(chunk_type),
+                index,
+            });
+        }
+
+        if let Some(iend) = iend {
+            if index > iend {
+                violations.push(Violation::ChunkAfterIend {
+                    chunk_type: String::from(chunk_type),
+                    index,
+                });
+            }
+        }
+
+        // `first_idat != Some(index)` implies an earlier IDAT, so `index - 1` cannot underflow
+        if chunk_type == "IDAT" && first_idat != Some(index) && types[index - 1] != "IDAT" {
+            violations.push(Violation::NonContiguousIdat { index });
+        }
+
+        let misplaced = |placement: Placement| {
+            return Violation::MisplacedChunk {
+                chunk_type: String::from(chunk_type),
+                index,
+                placement,
+            };
+        };
+        if chunk_type == "PLTE" || BEFORE_IDAT_CHUNKS.contains(&chunk_type) {
+            if let Some(first_idat) = first_idat {
+                if index > first_idat {
+                    violations.push(misplaced(Placement::BeforeIdat));
+                }
+            }
+        }
+        if BEFORE_PLTE_CHUNKS.contains(&chunk_type) {
+            if let Some(plte) = plte {
+                if index > plte {
+                    violations.push(misplaced(Placement::BeforePlte));
+                }
+            }
+        }
+        if AFTER_PLTE_CHUNKS.contains(&chunk_type) {
+            match plte {
+                Some(plte) if index < plte => violations.push(misplaced(Placement::AfterPlte)),
+                None if chunk_type == "hIST" => violations.push(misplaced(Placement::AfterPlte)),
+                _ => (),
+            }
+        }
+
+        if chunk_type == "sRGB" && position("iCCP").is_some() {
+            violations.push(Violation::ConflictingChunks {
+                chunk_type: String::from(chunk_type),
+                other: String::from("iCCP"),
+                index,
+            });
+        }
+
+        let is_critical = chunks[index].chunk_type().is_critical();
+        if is_critical && !CRITICAL_CHUNKS.contains(&chunk_type) {
+            violations.push(Violation::UnknownCriticalChunk {
+                chunk_type: String::from(chunk_type),
+                index,
+            });
+        }
+    }
+
+    return violations;
+}
+
+fn check_palette(header: &ImageHeader, plte: Option<usize>, violations: &mut Vec<Violation>) {
+    match (header.color_type(), plte) {
+        (ColorType::Indexed, None) => {
+            violations.push(Violation::MissingChunk(String::from("PLTE")));
+        }
+        (ColorType::Grayscale | ColorType::GrayscaleAlpha, Some(index)) => {
+            violations.push(Violation::ForbiddenPlte { index });
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str) -> Chunk {
+        let data = match chunk_type {
+            "IHDR" => vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0],
+            _ => Vec::new(),
+        };
+        return Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data).unwrap();
+    }
+
+    fn check(chunk_types: &[&str]) -> Vec<Violation> {
+        let chunks: Vec<Chunk> = chunk_types.iter().map(|t| chunk(t)).collect();
+        return validate(&chunks);
+    }
+
+    #[test]
+    pub fn test_validate_valid_files() {
+        assert!(check(&["IHDR", "PLTE", "IDAT", "IEND"]).is_empty());
+        assert!(check(&[
+            "IHDR", "gAMA", "sRGB", "PLTE", "tRNS", "bKGD", "pHYs", "tEXt", "IDAT", "IDAT", "IDAT",
+            "tEXt", "ruSt", "IEND"
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    pub fn test_validate_required_chunks() {
+        assert_eq!(
+            check(&["PLTE", "IDAT"]),
+            vec![
+                Violation::MissingChunk(String::from("IHDR")),
+                Violation::MissingChunk(String::from("IEND")),
+            ]
+        );
+        assert_eq!(
+            check(&["IHDR", "IDAT", "IEND"]),
+            vec![Violation::MissingChunk(String::from("PLTE"))]
+        );
+        assert_eq!(
+            check(&["PLTE", "IHDR", "IDAT", "IEND"]),
+            vec![Violation::IhdrNotFirst { index: 1 }]
+        );
+    }
+
+    #[test]
+    pub fn test_validate_ordering() {
+        assert_eq!(
+            check(&["IHDR", "PLTE", "IDAT", "tEXt", "IDAT", "IEND"]),
+            vec![Violation::NonContiguousIdat { index: 4 }]
+        );
+        assert_eq!(
+            check(&["IHDR", "IDAT", "PLTE", "IEND"]),
+            vec![Violation::MisplacedChunk {
+                chunk_type: String::from("PLTE"),
+                index: 2,
+                placement: Placement::BeforeIdat,
+            }]
+        );
+        assert_eq!(
+            check(&["IHDR", "PLTE", "gAMA", "IDAT", "IEND"]),
+            vec![Violation::MisplacedChunk {
+                chunk_type: String::from("gAMA"),
+                index: 2,
+                placement: Placement::BeforePlte,
+            }]
+        );
+        assert_eq!(
+            check(&["IHDR", "tRNS", "PLTE", "IDAT", "IEND"]),
+            vec![Violation::MisplacedChunk {
+                chunk_type: String::from("tRNS"),
+                index: 1,
+                placement: Placement::AfterPlte,
+            }]
+        );
+        assert_eq!(
+            check(&["IHDR", "PLTE", "IDAT", "IEND", "tEXt"]),
+            vec![Violation::ChunkAfterIend {
+                chunk_type: String::from("tEXt"),
+                index: 4,
+            }]
+        );
+    }
+
+    #[test]
+    pub fn test_validate_multiplicity() {
+        assert_eq!(
+            check(&["IHDR", "PLTE", "PLTE", "IDAT", "IEND"]),
+            vec![Violation::DuplicateChunk {
+                chunk_type: String::from("PLTE"),
+                index: 2,
+            }]
+        );
+        assert_eq!(
+            check(&["IHDR", "iCCP", "sRGB", "PLTE", "IDAT", "IEND"]),
+            vec![Violation::ConflictingChunks {
+                chunk_type: String::from("sRGB"),
+                other: String::from("iCCP"),
+                index: 2,
+            }]
+        );
+        assert_eq!(
+            check(&["IHDR", "PLTE", "RuSt", "IDAT", "IEND"]),
+            vec![Violation::UnknownCriticalChunk {
+                chunk_type: String::from("RuSt"),
+                index: 2,
+            }]
+        );
+    }
+}
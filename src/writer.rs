@@ -0,0 +1,55 @@
+use std::io::Write;
+
+use crate::chunk::Chunk;
+use crate::error::Result;
+use crate::png::Png;
+
+pub struct PngWriter<W: Write> {
+    m_writer: W,
+}
+
+impl<W: Write> PngWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&Png::STANDARD_HEADER)?;
+        return Ok(Self { m_writer: writer });
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        self.m_writer.write_all(&chunk.length().to_be_bytes())?;
+        self.m_writer.write_all(chunk.chunk_type().bytes())?;
+        self.m_writer.write_all(chunk.data())?;
+        self.m_writer.write_all(&chunk.crc().to_be_bytes())?;
+        return Ok(());
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.m_writer.flush()?;
+        return Ok(self.m_writer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::PngReader;
+
+    #[test]
+    pub fn test_writer_round_trip() {
+        let chunks = vec![
+            Chunk::from_str("FrSt", "I am the first chunk").unwrap(),
+            Chunk::from_str("miDl", "I am another chunk").unwrap(),
+            Chunk::from_str("LASt", "I am the last chunk").unwrap(),
+        ];
+
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+        for chunk in chunks.iter() {
+            writer.write_chunk(chunk).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        assert_eq!(bytes, Png::from_chunks(chunks.clone()).bytes());
+
+        let reader = PngReader::new(&bytes[..]).unwrap();
+        let read_chunks: Vec<Chunk> = reader.collect::<Result<Vec<Chunk>>>().unwrap();
+        assert_eq!(read_chunks, chunks);
+    }
+}
@@ -0,0 +1,239 @@
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::str::FromStr;
+
+use pngme::crypto;
+use pngme::deflate::CompressionLevel;
+use pngme::image::Image;
+use pngme::image_header::ColorType;
+use pngme::inflate::DEFAULT_OUTPUT_LIMIT;
+use pngme::lsb::LsbOptions;
+use pngme::ops::{self, ChunkChange, WriteOptions};
+use pngme::png::{ChunkPosition, ChunkSelection};
+use pngme::text_chunk::{TextChunk, TextEntry};
+use pngme::{Chunk, ChunkType, Error, Png};
+
+fn cover_png() -> Png {
+    let pixels: Vec<u8> = (0..(24 * 16 * 3)).map(|i| (i * 7 % 256) as u8).collect();
+    let image = Image::new(24, 16, ColorType::Rgb, 8, pixels).unwrap();
+    return image.to_png(CompressionLevel::Default).unwrap();
+}
+
+// a path in the temporary directory that no other test uses
+fn temp_path(name: &str) -> String {
+    let path = env::temp_dir().join(format!("pngme-{}-{}", std::process::id(), name));
+    return path.to_str().unwrap().to_string();
+}
+
+#[test]
+pub fn test_api_chunk_round_trip() {
+    let chunk_type = ChunkType::from_str("ruSt").unwrap();
+    assert!(!chunk_type.is_critical());
+    assert!(chunk_type.is_safe_to_copy());
+
+    let chunk = Chunk::new(chunk_type, b"secret".to_vec()).unwrap();
+    let mut png = cover_png();
+    png.insert_before_iend(chunk.clone());
+
+    let png = Png::from_bytes(&png.bytes()).unwrap();
+    assert_eq!(png.search_chunk("ruSt"), Some(&chunk));
+    assert!(png.validate().is_empty());
+    assert!(ChunkType::from_str("ru5t").is_err());
+}
+
+#[test]
+pub fn test_api_file_operations() {
+    let (src, dst) = (temp_path("ops-src.png"), temp_path("ops-dst.png"));
+    let options = WriteOptions::default();
+    ops::save_png(&src, &cover_png(), &options).unwrap();
+
+    ops::encode(&src, &dst, "ruSt", b"hidden", &options).unwrap();
+    assert_eq!(ops::decode(&dst, "ruSt").unwrap(), b"hidden");
+    assert!(matches!(
+        ops::decode(&src, "ruSt"),
+        Err(Error::ChunkNotFound(_))
+    ));
+
+    ops::encode(&dst, &dst, "ruSt", b"second", &options).unwrap();
+    assert_eq!(ops::decode_occurrence(&dst, "ruSt", 1).unwrap(), b"second");
+    assert!(matches!(
+        ops::decode_occurrence(&dst, "ruSt", 2),
+        Err(Error::InvalidOccurrence { count: 2, .. })
+    ));
+    assert!(matches!(
+        ops::decode_occurrence(&dst, "teXt", 0),
+        Err(Error::ChunkNotFound(_))
+    ));
+    // nothing selected, so nothing is written
+    let modified = fs::metadata(&dst).unwrap().modified().unwrap();
+    let is_empty = |chunk: &Chunk| chunk.data().is_empty();
+    let selection = ChunkSelection::Matching(&is_empty);
+    assert!(matches!(
+        ops::delete_chunks(&dst, &dst, "ruSt", selection, &options),
+        Err(Error::ChunkNotFound(_))
+    ));
+    assert_eq!(fs::metadata(&dst).unwrap().modified().unwrap(), modified);
+    let (count, _) =
+        ops::delete_chunks(&dst, &dst, "ruSt", ChunkSelection::Occurrence(1), &options).unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(
+        ops::decode_all(&dst, "ruSt").unwrap(),
+        vec![b"hidden".to_vec()]
+    );
+    ops::delete(&dst, "ruSt", &options).unwrap();
+    assert_eq!(ops::read_png(&dst).unwrap(), ops::read_png(&src).unwrap());
+
+    // a plain message that merely starts with the encryption magic is decoded as plain text
+    ops::encode(&src, &dst, "ruSt", b"PMEC hello", &options).unwrap();
+    let decoded = ops::decode(&dst, "ruSt").unwrap();
+    assert!(!crypto::is_encrypted(&decoded));
+    assert_eq!(decoded, b"PMEC hello");
+
+    fs::remove_file(&src).unwrap();
+    fs::remove_file(&dst).unwrap();
+}
+
+#[test]
+pub fn test_api_encode_at() {
+    let (src, dst) = (temp_path("at-src.png"), temp_path("at-dst.png"));
+    let options = WriteOptions::default();
+    ops::save_png(&src, &cover_png(), &options).unwrap();
+    let len = cover_png().chunks().len();
+
+    // the streamed insert lands where the in-memory one does
+    for position in [
+        ChunkPosition::End,
+        ChunkPosition::BeforeIend,
+        ChunkPosition::AfterIhdr,
+        ChunkPosition::BeforeIdat,
+        ChunkPosition::Index(0),
+        ChunkPosition::Index(len),
+        ChunkPosition::Before(String::from("IEND")),
+        ChunkPosition::After(String::from("IDAT")),
+    ] {
+        ops::encode_at(&src, &dst, "ruSt", b"hidden", &position, &options).unwrap();
+        let mut png = cover_png();
+        png.insert_chunk_at(&position, Chunk::from_str("ruSt", "hidden").unwrap())
+            .unwrap();
+        assert_eq!(ops::read_png(&dst).unwrap(), png);
+    }
+
+    fs::remove_file(&dst).unwrap();
+    let index = ChunkPosition::Index(len + 1);
+    assert!(matches!(
+        ops::encode_at(&src, &dst, "ruSt", b"hidden", &index, &options),
+        Err(Error::InvalidIndex { .. })
+    ));
+    let before = ChunkPosition::Before(String::from("PLTE"));
+    assert!(matches!(
+        ops::encode_at(&src, &dst, "ruSt", b"hidden", &before, &options),
+        Err(Error::ChunkNotFound(_))
+    ));
+    assert!(fs::metadata(&dst).is_err());
+
+    fs::remove_file(&src).unwrap();
+}
+
+#[test]
+pub fn test_api_pixel_operations() {
+    let (src, dst) = (temp_path("lsb-src.png"), temp_path("lsb-dst.png"));
+    ops::save_png(&src, &cover_png(), &WriteOptions::default()).unwrap();
+
+    let options = LsbOptions::new(Some("rg"), 2).unwrap();
+    let msg = crypto::encrypt_with_params(
+        b"hidden",
+        b"passphrase",
+        &crypto::KdfParams::new(8, 1, 1).unwrap(),
+    )
+    .unwrap();
+    ops::encode_lsb(
+        &src,
+        &dst,
+        &msg,
+        &options,
+        false,
+        None,
+        &WriteOptions::default(),
+    )
+    .unwrap();
+
+    let decoded = ops::decode_lsb(&dst, &options, None).unwrap();
+    assert!(crypto::is_encrypted(&decoded));
+    assert_eq!(crypto::decrypt(&decoded, b"passphrase").unwrap(), b"hidden");
+    let image = Image::from_png(&ops::read_png(&dst).unwrap(), DEFAULT_OUTPUT_LIMIT).unwrap();
+    assert_eq!((image.width(), image.height()), (24, 16));
+
+    fs::remove_file(&src).unwrap();
+    fs::remove_file(&dst).unwrap();
+}
+
+#[test]
+pub fn test_api_write_options() {
+    let src = temp_path("write-src.png");
+    let backup = format!("{}.bak", src);
+    let report = ops::save_png(&src, &cover_png(), &WriteOptions::default()).unwrap();
+    assert_eq!(report.old_length(), None);
+    assert_eq!(report.changes(), None);
+    assert_eq!(report.new_length(), fs::metadata(&src).unwrap().len());
+
+    // a dry run reports the new chunk but leaves the file alone
+    let original = fs::read(&src).unwrap();
+    let dry_run = WriteOptions::new(None, true, false).unwrap();
+    let report = ops::encode(&src, &src, "ruSt", b"hidden", &dry_run).unwrap();
+    assert!(report.is_dry_run());
+    let added = ChunkChange::Added {
+        index: 2,
+        chunk_type: String::from("ruSt"),
+        length: 6,
+    };
+    assert_eq!(report.changes(), Some(&[added.clone()][..]));
+    assert_eq!(fs::read(&src).unwrap(), original);
+
+    let options = WriteOptions::new(Some(".bak"), false, true).unwrap();
+    let report = ops::encode(&src, &src, "ruSt", b"hidden", &options).unwrap();
+    assert_eq!(report.backup_fname(), Some(backup.as_str()));
+    assert_eq!(fs::read(&backup).unwrap(), original);
+    assert_eq!(ops::decode(&src, "ruSt").unwrap(), b"hidden");
+    assert_eq!(report.changes(), Some(&[added][..]));
+
+    // an existing backup is never overwritten, and the file is left alone
+    let encoded = fs::read(&src).unwrap();
+    assert!(matches!(
+        ops::encode(&src, &src, "ruSt", b"again", &options),
+        Err(Error::BackupExists(_))
+    ));
+    assert_eq!(fs::read(&backup).unwrap(), original);
+    assert_eq!(fs::read(&src).unwrap(), encoded);
+
+    let (count, report) =
+        ops::delete_chunks(&src, &src, "ruSt", ChunkSelection::All, &dry_run).unwrap();
+    assert_eq!(count, 1);
+    assert!(matches!(
+        report.changes(),
+        Some([ChunkChange::Removed { index: 2, .. }])
+    ));
+
+    assert!(WriteOptions::new(Some(""), false, false).is_err());
+    assert!(WriteOptions::new(Some("/bak"), false, false).is_err());
+    // no temporary file is left behind
+    let dir = fs::read_dir(env::temp_dir()).unwrap();
+    assert!(!dir
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .any(|name| name.contains("write-src.png.") && name.ends_with(".tmp")));
+
+    fs::remove_file(&src).unwrap();
+    fs::remove_file(&backup).unwrap();
+}
+
+#[test]
+pub fn test_api_text_entries() {
+    let mut png = cover_png();
+    let entry = TextEntry::Text(TextChunk::new("Comment", "made with pngme").unwrap());
+    png.set_text(&entry).unwrap();
+
+    let entries = png.text_entries().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].text(), "made with pngme");
+}